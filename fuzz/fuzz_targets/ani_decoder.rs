@@ -0,0 +1,11 @@
+#![no_main]
+
+use atomic_bomberman::animation::Decoder;
+use libfuzzer_sys::fuzz_target;
+
+// Malformed ANI files must be rejected with an error, never panic.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mut decoder) = Decoder::new(data) {
+        let _ = decoder.load_animation_bundle();
+    }
+});
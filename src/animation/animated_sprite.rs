@@ -1,28 +1,45 @@
 use super::Animation;
 use bevy::ecs::bundle::Bundle;
 use bevy::prelude::*;
+use std::time::Duration;
 
 #[derive(Default)]
 pub struct AnimatedSpritePlugin;
 
-struct AnimationTimer(Timer);
-
-const ANIMATION_FPS: f32 = 25.;
-
 impl Plugin for AnimatedSpritePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(AnimationTimer(Timer::from_seconds(
-            1. / ANIMATION_FPS,
-            true,
-        )))
-        .add_system(animate_sprite);
+        app.add_event::<AnimationFinished>()
+            .add_system(animate_sprite);
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackMode {
+    /// Start over after the last frame.
+    Loop,
+    /// Stop at the last frame and send an `AnimationFinished` event.
+    Once,
+    /// Play forward and backward again, forever.
+    PingPong,
+}
+
 #[derive(Component)]
 pub struct AnimatedSprite {
     pub animation: Handle<Animation>,
     pub index: usize,
+    pub mode: PlaybackMode,
+    pub playing: bool,
+    /// Time the current frame has been shown. Every sprite keeps its own
+    /// clock, so it starts playing from the moment it is spawned.
+    pub elapsed: Duration,
+    /// Set while a ping-pong animation is playing backward.
+    reverse: bool,
+}
+
+/// Sent when an animation with `PlaybackMode::Once` has shown its last frame.
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub animation: Handle<Animation>,
 }
 
 #[derive(Bundle)]
@@ -31,9 +48,6 @@ pub struct AnimatedSpriteBundle {
     pub sprite_sheet: SpriteSheetBundle,
 
     pub animated_sprite: AnimatedSprite,
-    // TODO: playing: bool
-    // TODO: repeating?
-    // TODO: fire event when done?
 }
 
 impl AnimatedSpriteBundle {
@@ -47,32 +61,141 @@ impl AnimatedSpriteBundle {
         AnimatedSpriteBundle {
             sprite_sheet: SpriteSheetBundle {
                 texture_atlas: asset.atlas.texture.clone(),
-                sprite: TextureAtlasSprite::new(asset.frames[0].index),
+                sprite: TextureAtlasSprite {
+                    index: asset.frames[0].index,
+                    anchor: asset.anchor(0),
+                    ..Default::default()
+                },
                 transform,
                 ..Default::default()
             },
             animated_sprite: AnimatedSprite {
                 animation,
                 index: 0,
+                mode: PlaybackMode::Loop,
+                playing: true,
+                elapsed: Duration::ZERO,
+                reverse: false,
             },
         }
     }
+
+    pub fn with_mode(mut self, mode: PlaybackMode) -> Self {
+        self.animated_sprite.mode = mode;
+        self
+    }
+}
+
+impl AnimatedSprite {
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn resume(&mut self) {
+        self.playing = true;
+    }
+
+    /// Switch to another animation in place. With `keep_frame` playback
+    /// continues at the same frame index (wrapped to the length of the new
+    /// animation), otherwise it starts over at the first frame.
+    pub fn set_animation(
+        &mut self,
+        handle: Handle<Animation>,
+        animation: &Animation,
+        mode: PlaybackMode,
+        keep_frame: bool,
+    ) {
+        if keep_frame {
+            self.index %= animation.frames.len().max(1);
+        } else {
+            self.index = 0;
+            self.elapsed = Duration::ZERO;
+            self.reverse = false;
+        }
+
+        self.animation = handle;
+        self.mode = mode;
+        self.playing = true;
+    }
+
+    /// Advance to the next frame of an animation with `frame_count` frames.
+    /// Returns true when a play-once animation just finished.
+    fn advance(&mut self, frame_count: usize) -> bool {
+        let last = frame_count.saturating_sub(1);
+
+        match self.mode {
+            PlaybackMode::Loop => {
+                self.index = (self.index + 1) % frame_count.max(1);
+                false
+            }
+            PlaybackMode::Once => {
+                if self.index < last {
+                    self.index += 1;
+                    false
+                } else {
+                    self.playing = false;
+                    true
+                }
+            }
+            PlaybackMode::PingPong => {
+                if (self.reverse && self.index == 0) || (!self.reverse && self.index >= last) {
+                    self.reverse = !self.reverse;
+                }
+                if self.reverse {
+                    self.index = self.index.saturating_sub(1);
+                } else {
+                    self.index = (self.index + 1).min(last);
+                }
+                false
+            }
+        }
+    }
 }
 
 fn animate_sprite(
     time: Res<Time>,
     animation_assets: Res<Assets<Animation>>,
-    mut timer: ResMut<AnimationTimer>,
-    mut query: Query<(&mut TextureAtlasSprite, &mut AnimatedSprite)>,
+    mut finished_events: EventWriter<AnimationFinished>,
+    mut query: Query<(
+        Entity,
+        &mut TextureAtlasSprite,
+        &mut Handle<TextureAtlas>,
+        &mut AnimatedSprite,
+    )>,
 ) {
-    timer.0.tick(time.delta());
-    if timer.0.finished() {
-        for (mut sprite, mut animate_sprite) in query.iter_mut() {
-            let animation = animation_assets.get(&animate_sprite.animation).unwrap();
-            if animation.frames.len() > 1 {
-                animate_sprite.index = (animate_sprite.index + 1) % animation.frames.len();
-                sprite.index = animation.frames[animate_sprite.index].index;
+    for (entity, mut sprite, mut atlas, mut animate_sprite) in query.iter_mut() {
+        let animation = animation_assets.get(&animate_sprite.animation).unwrap();
+
+        // The animation may have been switched to one from another bundle.
+        if *atlas != animation.atlas.texture {
+            *atlas = animation.atlas.texture.clone();
+        }
+
+        if animate_sprite.playing {
+            animate_sprite.elapsed += time.delta();
+        }
+
+        // A slow frame can skip over several short animation frames, but at
+        // most one loop of them, so frames without a duration can't hang it.
+        for _ in 0..animation.frames.len() {
+            if !animate_sprite.playing {
+                break;
+            }
+            let duration = animation.frames[animate_sprite.index].duration;
+            if animate_sprite.elapsed < duration {
+                break;
+            }
+
+            animate_sprite.elapsed -= duration;
+            if animate_sprite.advance(animation.frames.len()) {
+                finished_events.send(AnimationFinished {
+                    entity,
+                    animation: animate_sprite.animation.clone(),
+                });
             }
         }
+
+        sprite.index = animation.frames[animate_sprite.index].index;
+        sprite.anchor = animation.anchor(animate_sprite.index);
     }
 }
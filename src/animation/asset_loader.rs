@@ -5,20 +5,41 @@ use bevy::{
     reflect::TypeUuid,
     render::render_resource::{Extent3d, TextureDimension, TextureFormat},
     render::texture::Image,
+    sprite::{Anchor, Rect},
     utils::BoxedFuture,
 };
 use byteorder::{ReadBytesExt, LE};
+use super::{
+    packer::{self, MAX_ATLAS_SIZE},
+    AnimationCache,
+};
 use std::collections::HashMap;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+/// Frames that don't specify how long they are shown play at 25 FPS.
+pub const DEFAULT_FRAME_DURATION: Duration = Duration::from_millis(40);
 
 /// Convert Bomberman specific ANI format to a sprite sheet.
 ///
 /// Inspired by:
 /// https://github.com/mmatyas/ab_aniex/blob/master/src/AniFile.cpp
 /// https://github.com/image-rs/image/blob/master/src/codecs/bmp/decoder.rs
-#[derive(Default)]
-pub struct AnimationAssetLoader;
+///
+/// Decoded bundles are kept in the `AnimationCache`, if there is one.
+pub struct AnimationAssetLoader {
+    cache: Option<AnimationCache>,
+}
+
+impl FromWorld for AnimationAssetLoader {
+    fn from_world(world: &mut World) -> Self {
+        AnimationAssetLoader {
+            cache: world.get_resource::<AnimationCache>().cloned(),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Frame {
@@ -27,17 +48,35 @@ pub struct Frame {
     pub height: u32,
     pub offset_x: i32,
     pub offset_y: i32,
+    /// Hotspot of the frame image, in pixels from its top left corner.
+    pub hotspot_x: i32,
+    pub hotspot_y: i32,
+    pub duration: Duration,
 }
 
+impl Frame {
+    /// The pixel of the frame image, from its top left corner, that is drawn
+    /// at the position of the sprite. The image is drawn with its hotspot
+    /// at the sequence origin, shifted by the offset of the frame.
+    pub fn origin(&self) -> Vec2 {
+        Vec2::new(
+            (self.hotspot_x - self.offset_x) as f32,
+            (self.hotspot_y - self.offset_y) as f32,
+        )
+    }
+}
+
+/// Texture atlas shared by the animations of a bundle. Frame images are
+/// packed into it unpadded, atlas index `i` holds the frame image `i`.
 #[derive(Debug, Clone)]
 pub struct AnimationAtlas {
-    pub tile_width: u32,
-    pub tile_height: u32,
-    pub tile_count: usize,
+    pub width: u32,
+    pub height: u32,
+    pub frame_count: usize,
     pub texture: Handle<TextureAtlas>,
 }
 
-#[derive(Debug, TypeUuid)]
+#[derive(Debug, Clone, TypeUuid)]
 #[uuid = "e1e9f49e-4fcd-464d-bb04-c8e60cf00422"]
 pub struct Animation {
     pub name: String,
@@ -45,12 +84,82 @@ pub struct Animation {
     pub height: u32,
     pub frames: Vec<Frame>,
     pub atlas: AnimationAtlas,
+    /// Collision box of the bundle the animation was loaded from.
+    pub collision_box: Option<CollisionBox>,
 }
 
 #[derive(Debug, TypeUuid)]
 #[uuid = "56c38dde-6ab4-4d02-93c1-976a7fa8dea2"]
 pub struct AnimationBundle {
     pub animations: HashMap<String, Handle<Animation>>,
+    /// Raw contents of the file HEAD, its meaning is unknown.
+    pub header: Vec<u8>,
+    /// Shared palette of the bundle, RGBA per entry.
+    pub palette: Vec<[u8; 4]>,
+    /// Transparency palette, `true` for palette indices that are transparent
+    /// in 8bpp frames.
+    pub transparency: Vec<bool>,
+    pub collision_box: Option<CollisionBox>,
+}
+
+/// Collision box in pixels, relative to the origin of a frame. It is usually
+/// smaller than the frames themselves, e.g. a player's hitbox excludes the
+/// head sticking into the cell above.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CollisionBox {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CollisionBox {
+    /// The box in world units around `center`, as `(min, max)`. The y axis
+    /// of the file points down, the one of the world up.
+    pub fn world_rect(&self, center: Vec2) -> (Vec2, Vec2) {
+        let min = Vec2::new(
+            center.x + self.x as f32,
+            center.y - (self.y + self.height as i32) as f32,
+        );
+        let max = Vec2::new(
+            center.x + (self.x + self.width as i32) as f32,
+            center.y - self.y as f32,
+        );
+        (min, max)
+    }
+}
+
+impl Animation {
+    /// Sprite anchor that puts the origin of frame `index` at the transform of
+    /// the sprite. The atlas rect of a frame is exactly the size of its image.
+    pub fn anchor(&self, index: usize) -> Anchor {
+        let frame = &self.frames[index];
+        let origin = frame.origin();
+        let size = Vec2::new(frame.width.max(1) as f32, frame.height.max(1) as f32);
+        let anchor = origin / size;
+        Anchor::Custom(Vec2::new(anchor.x - 0.5, 0.5 - anchor.y))
+    }
+}
+
+impl AnimationAssetLoader {
+    /// Decodes an ANI file, or takes it from the cache if it was decoded before.
+    fn decode(&self, bytes: &[u8], path: &Path) -> Result<DecodedAnimationBundle> {
+        if let Some(cached) = self.cache.as_ref().and_then(|cache| cache.get(bytes)) {
+            debug!("loaded {:?} from the cache", path);
+            return Ok(cached);
+        }
+
+        let decoded = match Decoder::new(bytes).and_then(|mut d| d.load_animation_bundle()) {
+            Ok(decoded) => decoded,
+            Err(err) => bail!("Error in {:?}: {}", path, err),
+        };
+        if let Some(cache) = &self.cache {
+            if let Err(err) = cache.insert(bytes, &decoded) {
+                warn!("Not caching {:?}: {:#}", path, err);
+            }
+        }
+        Ok(decoded)
+    }
 }
 
 impl AssetLoader for AnimationAssetLoader {
@@ -60,11 +169,15 @@ impl AssetLoader for AnimationAssetLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<()>> {
         Box::pin(async move {
-            let mut decoder = Decoder::new(bytes)?;
-            let bundle = match decoder.load_animation_bundle(load_context) {
-                Ok(bundle) => bundle,
-                Err(err) => bail!("Error in {:?}: {:?}", load_context.path(), err),
+            let decoded = if DecodedAnimationBundle::is_decoded(bytes) {
+                match DecodedAnimationBundle::from_bytes(bytes) {
+                    Ok(decoded) => decoded,
+                    Err(err) => bail!("Error in {:?}: {}", load_context.path(), err),
+                }
+            } else {
+                self.decode(bytes, load_context.path())?
             };
+            let bundle = decoded.into_assets(load_context);
             load_context.set_default_asset(LoadedAsset::new(bundle));
             Ok(())
         })
@@ -75,6 +188,247 @@ impl AssetLoader for AnimationAssetLoader {
     }
 }
 
+/// The contents of an ANI file, before they are turned into assets.
+pub struct DecodedAnimationBundle {
+    pub header: Vec<u8>,
+    pub palette: Vec<[u8; 4]>,
+    pub transparency: Vec<bool>,
+    pub collision_box: Option<CollisionBox>,
+    /// All frame images packed into one texture.
+    pub texture: Image,
+    /// Rect of each frame image in `texture`.
+    pub rects: Vec<Rect>,
+    /// Hotspot of each frame image, in pixels from its top left corner.
+    pub hotspots: Vec<(u32, u32)>,
+    /// The animations, their atlas is bound by `into_assets`.
+    pub animations: Vec<Animation>,
+}
+
+impl DecodedAnimationBundle {
+    /// RGBA pixels of frame image `index`, cut out of the packed texture.
+    pub fn frame_pixels(&self, index: usize) -> Option<Vec<u8>> {
+        let rect = self.rects.get(index)?;
+        let stride = 4 * self.texture.texture_descriptor.size.width as usize;
+        let (x, y) = (rect.min.x as usize, rect.min.y as usize);
+        let row_len = 4 * (rect.max.x - rect.min.x) as usize;
+        let rows = (rect.max.y - rect.min.y) as usize;
+
+        let pixels = (y..y + rows)
+            .flat_map(|row| {
+                let start = row * stride + 4 * x;
+                &self.texture.data[start..start + row_len]
+            })
+            .copied()
+            .collect();
+        Some(pixels)
+    }
+
+    fn into_assets(self, load_context: &mut LoadContext) -> AnimationBundle {
+        let size = self.texture.size();
+        let texture = load_context.set_labeled_asset("texture", LoadedAsset::new(self.texture));
+        let mut texture_atlas = TextureAtlas::new_empty(texture, size);
+        for rect in self.rects {
+            texture_atlas.add_texture(rect);
+        }
+
+        let texture_atlas_handle =
+            load_context.set_labeled_asset("texture_atlas", LoadedAsset::new(texture_atlas));
+
+        // Bind texture atlas in all animations and wrap them in an asset.
+        let animations = self
+            .animations
+            .into_iter()
+            .map(|mut animation| {
+                animation.atlas.texture = texture_atlas_handle.clone();
+                let name = animation.name.clone();
+                let handle = load_context.set_labeled_asset(&name, LoadedAsset::new(animation));
+                (name, handle)
+            })
+            .collect();
+
+        AnimationBundle {
+            animations,
+            header: self.header,
+            palette: self.palette,
+            transparency: self.transparency,
+            collision_box: self.collision_box,
+        }
+    }
+}
+
+/// Malformed ANI data.
+#[derive(Debug)]
+pub struct DecodeError {
+    /// Offset from the start of the file, of the item header or of the data
+    /// that could not be read.
+    pub offset: u64,
+    /// Signature of the innermost item the error occurred in, `None` when it
+    /// is not inside an item, e.g. in the file header.
+    pub signature: Option<[u8; 4]>,
+    pub kind: DecodeErrorKind,
+}
+
+#[derive(Debug)]
+pub enum DecodeErrorKind {
+    /// Reading failed, usually because the data ended early.
+    Io(io::Error),
+    InvalidSignature,
+    UnexpectedItem { expected: [u8; 4], found: [u8; 4] },
+    /// The item claims to extend past the end of the file.
+    ItemTooLong { length: u32 },
+    ItemTooShort { length: u32, minimum: u32 },
+    UnsupportedImageType(u16),
+    InvalidPalette { size: usize },
+    /// The image data ended before all pixels were decoded.
+    TruncatedImage { expected: usize, found: usize },
+    /// The decoded frames don't fit into a single atlas texture.
+    TooLarge { width: u32, height: u32 },
+    /// A sequence refers to a frame, but the file contains none.
+    MissingFrames,
+    EmptySequence,
+    InvalidName,
+}
+
+type DecodeResult<T> = std::result::Result<T, DecodeError>;
+
+impl DecodeError {
+    fn new(kind: DecodeErrorKind) -> Self {
+        DecodeError {
+            offset: 0,
+            signature: None,
+            kind,
+        }
+    }
+
+    /// Attribute the error to `item`, unless it already happened in one of
+    /// its children.
+    fn within(mut self, item: &Item) -> Self {
+        if self.signature.is_none() {
+            self.offset = item.offset();
+            self.signature = Some(item.signature);
+        }
+        self
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        DecodeError::new(DecodeErrorKind::Io(err))
+    }
+}
+
+impl From<DecodeErrorKind> for DecodeError {
+    fn from(kind: DecodeErrorKind) -> Self {
+        DecodeError::new(kind)
+    }
+}
+
+fn signature_str(signature: &[u8]) -> String {
+    String::from_utf8_lossy(signature).into_owned()
+}
+
+impl fmt::Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeErrorKind::Io(err) => write!(f, "{}", err),
+            DecodeErrorKind::InvalidSignature => write!(f, "ANI signature is invalid"),
+            DecodeErrorKind::UnexpectedItem { expected, found } => write!(
+                f,
+                "expected {} item, found {}",
+                signature_str(expected),
+                signature_str(found)
+            ),
+            DecodeErrorKind::ItemTooLong { length } => {
+                write!(f, "length {} exceeds the end of the file", length)
+            }
+            DecodeErrorKind::ItemTooShort { length, minimum } => {
+                write!(f, "item is too small: {} < {}", length, minimum)
+            }
+            DecodeErrorKind::UnsupportedImageType(t) => {
+                write!(f, "CIMG type {:#06x} is not supported", t)
+            }
+            DecodeErrorKind::InvalidPalette { size } => {
+                write!(f, "invalid CIMG palette size {}", size)
+            }
+            DecodeErrorKind::TruncatedImage { expected, found } => write!(
+                f,
+                "image data ended after {} of {} pixels",
+                found, expected
+            ),
+            DecodeErrorKind::TooLarge { width, height } => {
+                write!(f, "{}x{} pixels exceed the atlas size", width, height)
+            }
+            DecodeErrorKind::MissingFrames => write!(f, "sequence without frames to refer to"),
+            DecodeErrorKind::EmptySequence => write!(f, "sequence has no STAT items"),
+            DecodeErrorKind::InvalidName => write!(f, "sequence name is not valid UTF-8"),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.signature {
+            Some(signature) => write!(
+                f,
+                "{} in {} item at offset {:#x}",
+                self.kind,
+                signature_str(&signature),
+                self.offset
+            ),
+            None => write!(f, "{} at offset {:#x}", self.kind, self.offset),
+        }
+    }
+}
+
+/// Data that decodes, but is probably not what the file meant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeWarning {
+    /// A sequence frame refers to a frame image that doesn't exist, the last
+    /// frame image is used instead.
+    FrameIndexClamped {
+        offset: u64,
+        index: usize,
+        frame_count: usize,
+    },
+    /// An item where a SEQ was expected, it and everything after it is
+    /// ignored.
+    UnknownItem { offset: u64, signature: [u8; 4] },
+}
+
+impl fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeWarning::FrameIndexClamped {
+                offset,
+                index,
+                frame_count,
+            } => write!(
+                f,
+                "frame index {} of FRAM item at offset {:#x} exceeds the {} frames",
+                index, offset, frame_count
+            ),
+            DecodeWarning::UnknownItem { offset, signature } => write!(
+                f,
+                "ignored {} item at offset {:#x} and the rest of the file",
+                signature_str(signature),
+                offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            DecodeErrorKind::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Size of the signature, length and id in front of every item.
+const ITEM_HEADER_SIZE: u64 = 10;
+
 #[derive(Debug, Default)]
 struct Item {
     signature: [u8; 4],
@@ -99,8 +453,9 @@ fn print_bytes(bytes: &[u8]) {
 type ByteCursor<'a> = Cursor<&'a [u8]>;
 
 impl Item {
-    fn signature_str(&self) -> String {
-        String::from_utf8(self.signature.into()).unwrap()
+    /// Offset of the item header.
+    fn offset(&self) -> u64 {
+        self.start - ITEM_HEADER_SIZE
     }
 
     fn end(&self) -> u64 {
@@ -110,13 +465,13 @@ impl Item {
     fn dump(&self, cursor: &mut ByteCursor) -> Result<()> {
         let mut buf = vec![0; self.length as usize];
         cursor.read_exact(&mut buf)?;
-        println!("{} {:?}", self.signature_str(), self);
+        println!("{} {:?}", signature_str(&self.signature), self);
         print_bytes(&buf);
         Ok(())
     }
 
-    fn skip(&self, cursor: &mut ByteCursor) -> Result<()> {
-        cursor.seek(SeekFrom::Current(self.length as i64))?;
+    fn skip(&self, cursor: &mut ByteCursor) -> DecodeResult<()> {
+        cursor.seek(SeekFrom::Start(self.end()))?;
         Ok(())
     }
 }
@@ -124,16 +479,23 @@ impl Item {
 struct FrameImage {
     width: u32,
     height: u32,
+    hotspot_x: u32,
+    hotspot_y: u32,
     /// 32bit RGBA encoded pixels.
     data: Vec<u8>,
 }
 
+/// Decodes ANI files. Malformed data is reported as `DecodeError`, the decoder
+/// never panics on it.
 #[derive(Default, Debug)]
-struct Decoder<'a> {
+pub struct Decoder<'a> {
     cursor: ByteCursor<'a>,
     has_loaded_metadata: bool,
     id: u16,
     file_end: u64,
+    /// Pixels of all frame images decoded so far.
+    pixels: u64,
+    warnings: Vec<DecodeWarning>,
 }
 
 trait TgaReadPixel<T> {
@@ -220,7 +582,7 @@ where
 }
 
 impl<'a> Decoder<'a> {
-    fn new(bytes: &'a [u8]) -> Result<Self> {
+    pub fn new(bytes: &'a [u8]) -> DecodeResult<Self> {
         let mut decoder = Decoder {
             cursor: Cursor::new(bytes),
             ..Default::default()
@@ -230,7 +592,12 @@ impl<'a> Decoder<'a> {
         Ok(decoder)
     }
 
-    fn read_metadata(&mut self) -> Result<()> {
+    /// Oddities found while decoding so far.
+    pub fn warnings(&self) -> &[DecodeWarning] {
+        &self.warnings
+    }
+
+    fn read_metadata(&mut self) -> DecodeResult<()> {
         if !self.has_loaded_metadata {
             self.read_file_header()?;
             self.has_loaded_metadata = true;
@@ -239,11 +606,11 @@ impl<'a> Decoder<'a> {
         Ok(())
     }
 
-    fn read_file_header(&mut self) -> Result<()> {
+    fn read_file_header(&mut self) -> DecodeResult<()> {
         let mut signature = [0; 10];
         self.cursor.read_exact(&mut signature)?;
         if signature != *b"CHFILEANI " {
-            bail!("ANI signature is invalid");
+            return Err(DecodeErrorKind::InvalidSignature.into());
         }
 
         let file_length = self.cursor.read_u32::<LE>()?;
@@ -253,30 +620,100 @@ impl<'a> Decoder<'a> {
         Ok(())
     }
 
-    fn read_item(&mut self) -> Result<Item> {
-        let mut item = Item::default();
-        self.cursor.read_exact(&mut item.signature)?;
-        item.length = self.cursor.read_u32::<LE>()?;
-        item.id = self.cursor.read_u16::<LE>()?;
-        item.start = self.cursor.position();
+    /// Reads an item header. Items that extend past the end of the data are
+    /// rejected, so their contents can be allocated up front.
+    fn read_item(&mut self) -> DecodeResult<Item> {
+        let offset = self.cursor.position();
+        let read_header = |cursor: &mut ByteCursor| -> io::Result<Item> {
+            let mut item = Item::default();
+            cursor.read_exact(&mut item.signature)?;
+            item.length = cursor.read_u32::<LE>()?;
+            item.id = cursor.read_u16::<LE>()?;
+            item.start = cursor.position();
+            Ok(item)
+        };
+        let item = read_header(&mut self.cursor).map_err(|err| DecodeError {
+            offset,
+            ..err.into()
+        })?;
+
+        if item.end() > self.cursor.get_ref().len() as u64 {
+            let kind = DecodeErrorKind::ItemTooLong {
+                length: item.length,
+            };
+            return Err(DecodeError::new(kind).within(&item));
+        }
+
         Ok(item)
     }
 
-    fn parse_item(&mut self, signature: &[u8]) -> Result<Item> {
+    fn parse_item(&mut self, signature: &[u8; 4]) -> DecodeResult<Item> {
         let item = self.read_item()?;
         if item.signature != *signature {
-            bail!(
-                "Expected {} item, found {}",
-                String::from_utf8(signature.into()).unwrap(),
-                item.signature_str()
-            );
+            let kind = DecodeErrorKind::UnexpectedItem {
+                expected: *signature,
+                found: item.signature,
+            };
+            return Err(DecodeError::new(kind).within(&item));
         }
 
         Ok(item)
     }
 
+    /// The bundle PAL holds 4 byte entries, the same layout as the palettes
+    /// embedded in 8bpp CIMG items.
+    fn parse_palette(&mut self, item: &Item) -> DecodeResult<Vec<[u8; 4]>> {
+        let mut palette = Vec::with_capacity(item.length as usize / 4);
+        for _ in 0..item.length / 4 {
+            let mut entry = [0; 4];
+            self.cursor.read_exact(&mut entry)?;
+            palette.push(entry);
+        }
+        self.cursor.seek(SeekFrom::Start(item.end()))?;
+        Ok(palette)
+    }
+
+    /// TPAL holds one byte per palette index, non-zero when the index is
+    /// transparent.
+    fn parse_transparency(&mut self, item: &Item) -> DecodeResult<Vec<bool>> {
+        let mut buf = vec![0; item.length as usize];
+        self.cursor.read_exact(&mut buf)?;
+        Ok(buf.iter().map(|b| *b != 0).collect())
+    }
+
+    /// CBOX is either just the size of a box centered on the origin, or the
+    /// top left corner followed by the size.
+    fn parse_collision_box(&mut self, item: &Item) -> DecodeResult<Option<CollisionBox>> {
+        let collision_box = match item.length {
+            4..=7 => {
+                let width = self.cursor.read_u16::<LE>()? as u32;
+                let height = self.cursor.read_u16::<LE>()? as u32;
+                Some(CollisionBox {
+                    x: -(width as i32) / 2,
+                    y: -(height as i32) / 2,
+                    width,
+                    height,
+                })
+            }
+            8.. => Some(CollisionBox {
+                x: self.cursor.read_i16::<LE>()? as i32,
+                y: self.cursor.read_i16::<LE>()? as i32,
+                width: self.cursor.read_u16::<LE>()? as u32,
+                height: self.cursor.read_u16::<LE>()? as u32,
+            }),
+            _ => None,
+        };
+        self.cursor.seek(SeekFrom::Start(item.end()))?;
+
+        // Some bundles store an empty box.
+        Ok(collision_box.filter(|b| b.width > 0 && b.height > 0))
+    }
+
     /// Each frame consists of a HEAD, followed by a FNAM and a CIMG.
-    fn parse_frame(&mut self) -> Result<FrameImage> {
+    ///
+    /// Transparent pixels of 8bpp frames are given by `transparency`, when the
+    /// bundle has no TPAL the color of the hotspot pixel is transparent.
+    fn parse_frame(&mut self, fram: &Item, transparency: &[bool]) -> DecodeResult<FrameImage> {
         // Ignore HEAD.
         self.parse_item(b"HEAD")?.skip(&mut self.cursor)?;
 
@@ -287,17 +724,37 @@ impl<'a> Decoder<'a> {
             item = self.read_item()?;
         }
         if item.signature != *b"CIMG" {
-            bail!("Expected CIMG item in frame");
+            let kind = DecodeErrorKind::UnexpectedItem {
+                expected: *b"CIMG",
+                found: item.signature,
+            };
+            return Err(DecodeError::new(kind).within(&item));
         }
 
+        let image = self
+            .parse_image(&item, transparency)
+            .map_err(|err| err.within(&item))?;
+
+        // The image data doesn't always end exactly at the end of the frame,
+        // continue after it.
+        self.cursor.seek(SeekFrom::Start(fram.end()))?;
+
+        Ok(image)
+    }
+
+    fn parse_image(&mut self, item: &Item, transparency: &[bool]) -> DecodeResult<FrameImage> {
         if item.length < 32 {
-            bail!("CIMG is too small: {} < 32", item.length);
+            return Err(DecodeErrorKind::ItemTooShort {
+                length: item.length,
+                minimum: 32,
+            }
+            .into());
         }
 
         let bits_per_pixel = match self.cursor.read_u16::<LE>()? {
             0x0004 => 16,
             0x000b => 8,
-            other => bail!("CIMG type {:#06x} is not supported", other),
+            other => return Err(DecodeErrorKind::UnsupportedImageType(other).into()),
         };
 
         // Unknown field.
@@ -309,7 +766,7 @@ impl<'a> Decoder<'a> {
             if additional_size > 32 {
                 palette_size = Some(additional_size - 32);
             } else {
-                bail!("CIMG palette is missing!");
+                return Err(DecodeErrorKind::InvalidPalette { size: 0 }.into());
             }
         };
 
@@ -322,8 +779,6 @@ impl<'a> Decoder<'a> {
         let hotspot_y = self.cursor.read_u16::<LE>()? as u32;
         let transparent = self.cursor.read_u16::<LE>()?;
 
-        // println!("hotspot = {}x{} transparent {:#06x}", hotspot_x, hotspot_y, transparent);
-
         // Unknown field.
         let _unknown3 = self.cursor.read_u16::<LE>()?;
 
@@ -332,6 +787,10 @@ impl<'a> Decoder<'a> {
             let _unknown4 = self.cursor.read_u32::<LE>()?;
             let _unknown5 = self.cursor.read_u32::<LE>()?;
 
+            if self.cursor.position() + size as u64 > item.end() {
+                return Err(DecodeErrorKind::InvalidPalette { size }.into());
+            }
+
             let mut buf = vec![0; size];
             self.cursor.read_exact(&mut buf)?;
             palette = Some(buf.chunks(4).map(|c| c.to_vec()).collect());
@@ -341,27 +800,40 @@ impl<'a> Decoder<'a> {
         let _unknown6 = self.cursor.read_u16::<LE>()?;
         let _unknown7 = self.cursor.read_u16::<LE>()?;
 
-        // println!("{:#06x} {:#06x}", _unknown6, _unknown7);
-
-        let _compressed_size = self.cursor.read_u32::<LE>()? - 12;
+        // Both sizes include the 12 bytes of this header.
+        let _compressed_size = self.cursor.read_u32::<LE>()?;
         let _uncompressed_size = self.cursor.read_u32::<LE>()?;
 
+        // Reject frames that can't fit into the atlas before decoding them.
+        let pixels = (width * height) as u64;
+        if width > MAX_ATLAS_SIZE
+            || height > MAX_ATLAS_SIZE
+            || self.pixels + pixels > (MAX_ATLAS_SIZE * MAX_ATLAS_SIZE) as u64
+        {
+            return Err(DecodeErrorKind::TooLarge { width, height }.into());
+        }
+        self.pixels += pixels;
+
+        let pixels = pixels as usize;
         let data: Vec<u8> = if bits_per_pixel == 16 {
-            if palette_size.is_some() {
-                // println!("{:?}", palette.unwrap());
-                // println!(
-                //     "CIMG 16bpp expected no palette, size found {}",
-                //     palette_size.unwrap()
-                // );
+            let raw_data = TgaRleIterator::new(&mut self.cursor)
+                .take(pixels)
+                .collect::<Vec<u16>>();
+            if raw_data.len() != pixels {
+                return Err(DecodeErrorKind::TruncatedImage {
+                    expected: pixels,
+                    found: raw_data.len(),
+                }
+                .into());
             }
 
             // Convert the 16 bit color values to 32bit RGBA. The 16 bit pixel
             // format has support for an alpha channel (the highest bit) but
             // that is not used. Instead alpha is set to all pixels that match
             // `transparent`.
-            TgaRleIterator::new(&mut self.cursor)
-                .take((width * height) as usize)
-                .flat_map(|v: u16| {
+            raw_data
+                .iter()
+                .flat_map(|&v| {
                     if v == transparent || v & 0b1000_0000_0000_0000 != 0 {
                         vec![0, 0, 0, 0]
                     } else {
@@ -373,25 +845,43 @@ impl<'a> Decoder<'a> {
                 })
                 .collect()
         } else {
-            if palette_size.is_none() || palette_size.unwrap() != 1024 {
-                bail!("CIMG 8bpp expected a palette size of 1024");
-            }
+            let palette = match palette {
+                Some(palette) if palette_size == Some(1024) => palette,
+                _ => {
+                    let size = palette_size.unwrap_or(0);
+                    return Err(DecodeErrorKind::InvalidPalette { size }.into());
+                }
+            };
 
             // Decode the TGA RLE encoding into 8bpp raw data.
             let raw_data = TgaRleIterator::new(&mut self.cursor)
-                .take((width * height) as usize)
+                .take(pixels)
                 .collect::<Vec<u8>>();
+            if raw_data.len() != pixels {
+                return Err(DecodeErrorKind::TruncatedImage {
+                    expected: pixels,
+                    found: raw_data.len(),
+                }
+                .into());
+            }
 
-            let hotspot_idx = hotspot_x + width * hotspot_y;
-            let hotspot = raw_data[hotspot_idx as usize];
-
-            let palette = palette.unwrap();
+            let is_transparent: Box<dyn Fn(u8) -> bool> = if transparency.contains(&true) {
+                Box::new(|v| transparency.get(v as usize).copied().unwrap_or(false))
+            } else {
+                // The hotspot may lie outside of the image.
+                let hotspot = if hotspot_x < width {
+                    raw_data.get((hotspot_x + width * hotspot_y) as usize).copied()
+                } else {
+                    None
+                };
+                Box::new(move |v| Some(v) == hotspot)
+            };
 
             // Convert the 8 bit color values via the palette to 32 bits RGBA.
             raw_data
                 .iter()
                 .flat_map(|v| {
-                    if *v == hotspot {
+                    if is_transparent(*v) {
                         vec![0, 0, 0, 0]
                     } else {
                         palette[*v as usize].clone()
@@ -400,80 +890,52 @@ impl<'a> Decoder<'a> {
                 .collect()
         };
 
-        // For some reason one byte is not consumed?
-        self.cursor.read_u8()?;
-
         Ok(FrameImage {
             width,
             height,
+            hotspot_x,
+            hotspot_y,
             data,
         })
     }
 
     /// Sequences are composed of a HEAD followed by one or more STAT items.
-    /// The HEAD contains the "name". Each STAT item contains a HEAD (which we
-    /// ignore) and a FRAM. The FRAM contains a "frame index".
+    /// The HEAD contains the "name". Each STAT item contains a HEAD and a
+    /// FRAM. The HEAD holds the frame timing, the FRAM contains a "frame
+    /// index".
     fn parse_animation(
         &mut self,
-        seq: Item,
+        seq: &Item,
         frame_images: &[FrameImage],
         atlas: AnimationAtlas,
-    ) -> Result<Animation> {
-        let mut item = self.read_item()?;
-        if item.signature != *b"HEAD" {
-            bail!("Expected a HEAD item inside SEQ");
-        }
-
+        collision_box: Option<CollisionBox>,
+    ) -> DecodeResult<Animation> {
         // We're only interested in the nul-terminated string in HEAD.
+        let item = self.parse_item(b"HEAD")?;
         let mut buf = vec![0; item.length as usize];
         self.cursor.read_exact(&mut buf)?;
         let str_bytes = buf.iter().cloned().take_while(|b| *b != 0).collect();
-        let name = String::from_utf8(str_bytes).unwrap();
+        let name = String::from_utf8(str_bytes)
+            .map_err(|_| DecodeError::new(DecodeErrorKind::InvalidName).within(&item))?;
 
         let mut frames = vec![];
 
         while self.cursor.position() < seq.end() {
-            item = self.read_item()?;
-            if item.signature != *b"STAT" {
-                bail!(
-                    "Expected STAT items after HEAD in SEQ, got {:?}",
-                    item.signature
-                );
-            }
-
-            self.parse_item(b"HEAD")?.skip(&mut self.cursor)?;
-            let fram_item = self.parse_item(b"FRAM")?;
-
-            let _id = self.cursor.read_u16::<LE>()?;
-            let index = self.cursor.read_u16::<LE>()? as usize;
-            // XXX: for one ANI file an index is too large, this fixes it.
-            let index = index.min(frame_images.len() - 1);
-
-            let offset_x = self.cursor.read_i16::<LE>()? as i32;
-            let offset_y = self.cursor.read_i16::<LE>()? as i32;
-
-            // Padding? Is always 0x00000000
-            let _unknown3 = self.cursor.read_u32::<LE>()?;
-
-            // Sometimes the FRAM is longer, usually it's 12 bytes, but there
-            // are cases of 22 bytes.
-            self.cursor.seek(SeekFrom::Start(fram_item.end()))?;
-
-            let image = &frame_images[index];
+            let stat = self.parse_item(b"STAT")?;
+            let frame = self
+                .parse_animation_frame(frame_images)
+                .map_err(|err| err.within(&stat))?;
+            frames.push(frame);
+        }
 
-            frames.push(Frame {
-                index,
-                offset_x,
-                offset_y,
-                width: image.width,
-                height: image.height,
-            });
+        if frames.is_empty() {
+            return Err(DecodeErrorKind::EmptySequence.into());
         }
 
-        let width = frames.iter().map(|f| f.width).max().unwrap();
-        let height = frames.iter().map(|f| f.height).max().unwrap();
+        let width = frames.iter().map(|f| f.width).max().unwrap_or(0);
+        let height = frames.iter().map(|f| f.height).max().unwrap_or(0);
 
-        println!("loaded animation {} ({}x{})", name, width, height);
+        debug!("loaded animation {} ({}x{})", name, width, height);
 
         Ok(Animation {
             name,
@@ -481,80 +943,147 @@ impl<'a> Decoder<'a> {
             width,
             height,
             atlas,
+            collision_box,
         })
     }
 
-    fn load_animation_bundle(&mut self, load_context: &mut LoadContext) -> Result<AnimationBundle> {
-        for sig in &[b"HEAD", b"PAL ", b"TPAL", b"CBOX"] {
-            let item = self.parse_item(*sig)?;
-            if item.signature == *b"PAL " {
-                // item.dump(&mut self.cursor)?;
-                item.skip(&mut self.cursor)?;
-            } else {
-                item.skip(&mut self.cursor)?;
+    /// A STAT item inside a SEQ.
+    fn parse_animation_frame(&mut self, frame_images: &[FrameImage]) -> DecodeResult<Frame> {
+        // The STAT HEAD starts with the time the frame is shown, in
+        // milliseconds. Zero means the frame plays at the default rate.
+        let head = self.parse_item(b"HEAD")?;
+        let duration = match head.length {
+            0..=1 => 0,
+            _ => self.cursor.read_u16::<LE>()?,
+        };
+        let duration = match duration {
+            0 => DEFAULT_FRAME_DURATION,
+            ms => Duration::from_millis(ms as u64),
+        };
+        self.cursor.seek(SeekFrom::Start(head.end()))?;
+
+        let fram_item = self.parse_item(b"FRAM")?;
+        let read_fram = |cursor: &mut ByteCursor| -> io::Result<_> {
+            let _id = cursor.read_u16::<LE>()?;
+            let index = cursor.read_u16::<LE>()? as usize;
+            let offset_x = cursor.read_i16::<LE>()? as i32;
+            let offset_y = cursor.read_i16::<LE>()? as i32;
+            Ok((index, offset_x, offset_y))
+        };
+        let (index, offset_x, offset_y) = read_fram(&mut self.cursor)
+            .map_err(|err| DecodeError::from(err).within(&fram_item))?;
+
+        // XXX: for one ANI file an index is too large, this fixes it.
+        let index = match frame_images.len() {
+            0 => return Err(DecodeError::new(DecodeErrorKind::MissingFrames).within(&fram_item)),
+            len if index >= len => {
+                self.warnings.push(DecodeWarning::FrameIndexClamped {
+                    offset: fram_item.offset(),
+                    index,
+                    frame_count: len,
+                });
+                len - 1
             }
-        }
+            _ => index,
+        };
+
+        // Sometimes the FRAM is longer, usually it's 12 bytes, but there
+        // are cases of 22 bytes. The remainder is padding, usually zero.
+        self.cursor.seek(SeekFrom::Start(fram_item.end()))?;
+
+        let image = &frame_images[index];
+
+        Ok(Frame {
+            index,
+            offset_x,
+            offset_y,
+            width: image.width,
+            height: image.height,
+            hotspot_x: image.hotspot_x as i32,
+            hotspot_y: image.hotspot_y as i32,
+            duration,
+        })
+    }
+
+    /// Decodes the whole file: the bundle header, all frame images packed into
+    /// one texture and the animations that play them.
+    pub fn load_animation_bundle(&mut self) -> DecodeResult<DecodedAnimationBundle> {
+        let item = self.parse_item(b"HEAD")?;
+        let mut header = vec![0; item.length as usize];
+        self.cursor
+            .read_exact(&mut header)
+            .map_err(|err| DecodeError::from(err).within(&item))?;
+
+        let item = self.parse_item(b"PAL ")?;
+        let palette = self
+            .parse_palette(&item)
+            .map_err(|err| err.within(&item))?;
+
+        let item = self.parse_item(b"TPAL")?;
+        let transparency = self
+            .parse_transparency(&item)
+            .map_err(|err| err.within(&item))?;
+
+        let item = self.parse_item(b"CBOX")?;
+        let collision_box = self
+            .parse_collision_box(&item)
+            .map_err(|err| err.within(&item))?;
 
         let mut frames = vec![];
         let mut item = self.read_item()?;
         while item.signature == *b"FRAM" {
-            frames.push(self.parse_frame()?);
+            let frame = self
+                .parse_frame(&item, &transparency)
+                .map_err(|err| err.within(&item))?;
+            frames.push(frame);
             item = self.read_item()?;
         }
 
-        let tile_width = frames.iter().map(|i| i.width).max().unwrap();
-        let tile_height = frames.iter().map(|i| i.height).max().unwrap();
-        let tile_count = frames.len();
+        let sizes: Vec<(u32, u32)> = frames.iter().map(|f| (f.width, f.height)).collect();
+        let hotspots = frames.iter().map(|f| (f.hotspot_x, f.hotspot_y)).collect();
+        let ((width, height), rects) = packer::pack(&sizes)
+            .map_err(|(width, height)| DecodeErrorKind::TooLarge { width, height })?;
 
         let atlas = AnimationAtlas {
-            tile_width,
-            tile_height,
-            tile_count,
+            width,
+            height,
+            frame_count: frames.len(),
             texture: Default::default(),
         };
 
         let mut animations = vec![];
         while item.signature == *b"SEQ " {
-            animations.push(self.parse_animation(item, &frames, atlas.clone())?);
+            let animation = self
+                .parse_animation(&item, &frames, atlas.clone(), collision_box)
+                .map_err(|err| err.within(&item))?;
+            animations.push(animation);
             if self.cursor.position() >= self.file_end {
                 break;
             }
             item = self.read_item()?;
         }
+        if item.signature != *b"SEQ " {
+            self.warnings.push(DecodeWarning::UnknownItem {
+                offset: item.offset(),
+                signature: item.signature,
+            });
+        }
 
-        // Build sprite sheet in vertical direction. Vertical because that
-        // allows us to just concat all the image data together.
-        //
-        // Each frame is right-paded with transparent pixels to match it's width
-        // to the widest frame. And bottom-padded with transparent rows to match
-        // the height of the highest frame. We need a constant tile size for the
-        // texture atlas to work.
-        let image_data = frames
-            .drain(0..)
-            .flat_map(|mut frame| {
-                if frame.width < tile_width {
-                    for y in 0..frame.height {
-                        let idx = frame.width + (y * tile_width);
-                        let idx = 4 * idx as usize;
-                        let extra = vec![0; 4 * (tile_width - frame.width) as usize];
-                        frame.data.splice(idx..idx, extra);
-                    }
-                }
-                if frame.height < tile_height {
-                    let rows = tile_height - frame.height;
-                    frame
-                        .data
-                        .extend_from_slice(&vec![0; (4 * tile_width * rows) as usize]);
-                }
-
-                frame.data
-            })
-            .collect();
+        // Copy every frame image row by row into its packed rect.
+        let stride = 4 * width as usize;
+        let mut image_data = vec![0; stride * height as usize];
+        for (frame, rect) in frames.iter().zip(rects.iter()) {
+            let row_len = 4 * frame.width as usize;
+            for (y, row) in frame.data.chunks_exact(row_len.max(1)).enumerate() {
+                let start = (rect.y as usize + y) * stride + 4 * rect.x as usize;
+                image_data[start..start + row_len].copy_from_slice(row);
+            }
+        }
 
         let texture = Image::new(
             Extent3d {
-                width: tile_width,
-                height: tile_height * tile_count as u32,
+                width,
+                height,
                 depth_or_array_layers: 1,
             },
             TextureDimension::D2,
@@ -562,28 +1091,179 @@ impl<'a> Decoder<'a> {
             TextureFormat::Rgba8UnormSrgb,
         );
 
-        let texture = load_context.set_labeled_asset("texture", LoadedAsset::new(texture));
-        let texture_atlas = TextureAtlas::from_grid(
+        let rects = rects
+            .iter()
+            .map(|rect| Rect {
+                min: Vec2::new(rect.x as f32, rect.y as f32),
+                max: Vec2::new((rect.x + rect.width) as f32, (rect.y + rect.height) as f32),
+            })
+            .collect();
+
+        Ok(DecodedAnimationBundle {
+            header,
+            palette,
+            transparency,
+            collision_box,
             texture,
-            Vec2::new(tile_width as f32, tile_height as f32),
-            1,
-            tile_count,
-        );
+            rects,
+            hotspots,
+            animations,
+        })
+    }
+}
 
-        let texture_atlas_handle =
-            load_context.set_labeled_asset("texture_atlas", LoadedAsset::new(texture_atlas));
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Bind texture atlas in all animations and wrap them in an asset.
-        let animations = animations
-            .drain(..)
-            .map(|mut animation| {
-                animation.atlas.texture = texture_atlas_handle.clone();
-                let name = animation.name.clone();
-                let handle = load_context.set_labeled_asset(&name, LoadedAsset::new(animation));
-                (name, handle)
-            })
-            .collect();
+    const FILE_HEADER_SIZE: u64 = 16;
+
+    fn item(signature: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut data = signature.to_vec();
+        data.extend((body.len() as u32).to_le_bytes());
+        data.extend(0u16.to_le_bytes());
+        data.extend(body);
+        data
+    }
+
+    fn file(body: &[u8]) -> Vec<u8> {
+        let mut data = b"CHFILEANI ".to_vec();
+        data.extend((body.len() as u32).to_le_bytes());
+        data.extend(0u16.to_le_bytes());
+        data.extend(body);
+        data
+    }
+
+    /// The HEAD, PAL, TPAL and CBOX items every file starts with.
+    fn bundle_header() -> Vec<u8> {
+        let mut data = item(b"HEAD", &[]);
+        data.extend(item(b"PAL ", &[]));
+        data.extend(item(b"TPAL", &[]));
+        data.extend(item(b"CBOX", &[]));
+        data
+    }
+
+    /// A FRAM with an 8bpp image, `pixels` are stored as one raw packet.
+    fn frame(width: u16, height: u16, hotspot: (u16, u16), pixels: &[u8]) -> Vec<u8> {
+        let mut cimg = vec![];
+        cimg.extend(0x000bu16.to_le_bytes());
+        cimg.extend(0u16.to_le_bytes());
+        cimg.extend((32 + 1024u32).to_le_bytes());
+        cimg.extend(0u32.to_le_bytes());
+        for v in [width, height, hotspot.0, hotspot.1, 0, 0] {
+            cimg.extend(v.to_le_bytes());
+        }
+        cimg.extend([0; 8]);
+        cimg.extend((0..=255u8).flat_map(|i| [i, i, i, 255]));
+        cimg.extend([0; 12]);
+        cimg.push(pixels.len() as u8 - 1);
+        cimg.extend(pixels);
+
+        let mut fram = item(b"HEAD", &[0; 4]);
+        fram.extend(item(b"CIMG", &cimg));
+        item(b"FRAM", &fram)
+    }
+
+    /// A SEQ that shows frame image `index` once.
+    fn sequence(name: &[u8], index: u16) -> Vec<u8> {
+        let mut fram = vec![];
+        for v in [0u16, index, 0, 0] {
+            fram.extend(v.to_le_bytes());
+        }
+        let mut stat = item(b"HEAD", &[0; 2]);
+        stat.extend(item(b"FRAM", &fram));
+
+        let mut seq = item(b"HEAD", name);
+        seq.extend(item(b"STAT", &stat));
+        item(b"SEQ ", &seq)
+    }
+
+    fn decode(bytes: &[u8]) -> DecodeResult<DecodedAnimationBundle> {
+        Decoder::new(bytes)?.load_animation_bundle()
+    }
+
+    #[test]
+    fn sequence_without_frames() {
+        let body = [bundle_header(), sequence(b"stand\0", 0)].concat();
+        let err = decode(&file(&body)).err().unwrap();
+
+        // The FRAM inside the STAT, after the SEQ, HEAD, STAT and STAT HEAD.
+        let seq = FILE_HEADER_SIZE + bundle_header().len() as u64;
+        assert!(matches!(err.kind, DecodeErrorKind::MissingFrames));
+        assert_eq!(err.signature, Some(*b"FRAM"));
+        assert_eq!(err.offset, seq + 10 + (10 + 6) + 10 + (10 + 2));
+    }
+
+    #[test]
+    fn hotspot_outside_of_the_image() {
+        // Without a TPAL the color at the hotspot is transparent, a hotspot
+        // outside of the image makes no color transparent.
+        for hotspot in [(5, 0), (1, 1000), (u16::MAX, u16::MAX)] {
+            let body = [
+                bundle_header(),
+                frame(2, 2, hotspot, &[1, 2, 3, 4]),
+                sequence(b"stand\0", 0),
+            ]
+            .concat();
+            let bundle = decode(&file(&body)).unwrap();
+            let pixels = bundle.frame_pixels(0).unwrap();
+            assert!(pixels.chunks(4).all(|p| p[3] == 255), "{:?}", hotspot);
+        }
+
+        let body = [
+            bundle_header(),
+            frame(2, 2, (1, 1), &[1, 2, 3, 4]),
+            sequence(b"stand\0", 0),
+        ]
+        .concat();
+        let bundle = decode(&file(&body)).unwrap();
+        assert_eq!(bundle.frame_pixels(0).unwrap()[12..], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn invalid_sequence_name() {
+        let frame = frame(1, 1, (0, 0), &[1]);
+        let body = [bundle_header(), frame.clone(), sequence(b"\xff\xfe\0", 0)].concat();
+        let err = decode(&file(&body)).err().unwrap();
+
+        // The HEAD right after the SEQ header.
+        let seq = FILE_HEADER_SIZE + (bundle_header().len() + frame.len()) as u64;
+        assert!(matches!(err.kind, DecodeErrorKind::InvalidName));
+        assert_eq!(err.signature, Some(*b"HEAD"));
+        assert_eq!(err.offset, seq + 10);
+    }
+
+    #[test]
+    fn item_longer_than_the_file() {
+        let mut body = [bundle_header(), frame(1, 1, (0, 0), &[1])].concat();
+        let fram = bundle_header().len();
+        body[fram + 4..fram + 8].copy_from_slice(&100_000u32.to_le_bytes());
+        let err = decode(&file(&body)).err().unwrap();
+
+        assert!(matches!(
+            err.kind,
+            DecodeErrorKind::ItemTooLong { length: 100_000 }
+        ));
+        assert_eq!(err.signature, Some(*b"FRAM"));
+        assert_eq!(err.offset, FILE_HEADER_SIZE + fram as u64);
+    }
 
-        Ok(AnimationBundle { animations })
+    #[test]
+    fn truncated_item() {
+        let body = [bundle_header(), sequence(b"stand\0", 0)].concat();
+        let bytes = file(&body);
+
+        // Cut in the middle of the SEQ header.
+        let seq = FILE_HEADER_SIZE as usize + bundle_header().len();
+        let err = decode(&bytes[..seq + 6]).err().unwrap();
+        assert!(matches!(err.kind, DecodeErrorKind::Io(_)));
+        assert_eq!(err.signature, None);
+        assert_eq!(err.offset, seq as u64);
+
+        // Cut inside the SEQ, which then claims to extend past the end.
+        let err = decode(&bytes[..seq + 20]).err().unwrap();
+        assert!(matches!(err.kind, DecodeErrorKind::ItemTooLong { .. }));
+        assert_eq!(err.signature, Some(*b"SEQ "));
+        assert_eq!(err.offset, seq as u64);
     }
 }
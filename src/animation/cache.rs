@@ -0,0 +1,124 @@
+use super::{decoded::DECODER_VERSION, DecodedAnimationBundle};
+use anyhow::{Context, Result};
+use bevy::asset::FileAssetIo;
+use std::{fs, path::PathBuf};
+
+/// On-disk cache of decoded animation bundles, so ANI files are decoded and
+/// packed only once instead of on every start.
+///
+/// Entries are named after a hash of the source file, a changed file gets a
+/// new entry and the old one is never read again. The hash includes the
+/// `DECODER_VERSION`, so entries of an older decoder are not used. Entries
+/// are stored with `DecodedAnimationBundle::to_bytes`, unreadable ones are
+/// decoded again and replaced. The directory can be removed at any time.
+///
+/// `AnimationAssetLoader` uses the `AnimationCache` resource, insert one
+/// before `CustomAssetLoaders` to use another directory.
+#[derive(Debug, Clone)]
+pub struct AnimationCache {
+    dir: PathBuf,
+}
+
+impl Default for AnimationCache {
+    fn default() -> Self {
+        AnimationCache::new(FileAssetIo::get_root_path().join("cache/animations"))
+    }
+}
+
+impl AnimationCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        AnimationCache { dir: dir.into() }
+    }
+
+    fn entry_path(&self, source: &[u8]) -> PathBuf {
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, &DECODER_VERSION.to_le_bytes());
+        hash = fnv1a(hash, source);
+        self.dir.join(format!("{:016x}-{}.bin", hash, source.len()))
+    }
+
+    /// The cached bundle of the ANI file `source`, if there is a valid one.
+    pub fn get(&self, source: &[u8]) -> Option<DecodedAnimationBundle> {
+        let bytes = fs::read(self.entry_path(source)).ok()?;
+        DecodedAnimationBundle::from_bytes(&bytes).ok()
+    }
+
+    pub fn insert(&self, source: &[u8], bundle: &DecodedAnimationBundle) -> Result<()> {
+        fs::create_dir_all(&self.dir).with_context(|| format!("Creating {:?}", self.dir))?;
+
+        // Written next to the entry and renamed, so a concurrent `get` never
+        // sees a partial entry.
+        let path = self.entry_path(source);
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp, bundle.to_bytes()).with_context(|| format!("Writing {:?}", tmp))?;
+        fs::rename(&tmp, &path).with_context(|| format!("Writing {:?}", path))?;
+        Ok(())
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64 bit FNV-1a, unlike `DefaultHasher` it is the same for every build.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{Decoder, Encoder, EncoderFrame, EncoderSequence, EncoderSequenceFrame};
+    use std::time::Duration;
+
+    fn encode(hotspot_x: u32) -> Vec<u8> {
+        Encoder {
+            header: vec![],
+            collision_box: None,
+            frames: vec![EncoderFrame {
+                name: "frame.png".into(),
+                width: 2,
+                height: 2,
+                hotspot_x,
+                hotspot_y: 0,
+                pixels: vec![255; 16],
+            }],
+            sequences: vec![EncoderSequence {
+                name: "stand".into(),
+                frames: vec![EncoderSequenceFrame {
+                    frame: 0,
+                    offset_x: 0,
+                    offset_y: 0,
+                    duration: Duration::from_millis(40),
+                }],
+            }],
+        }
+        .encode()
+        .unwrap()
+    }
+
+    #[test]
+    fn invalidated_by_changes() {
+        let dir = std::env::temp_dir().join(format!("animation_cache_{}", std::process::id()));
+        let cache = AnimationCache::new(&dir);
+
+        let source = encode(0);
+        assert!(cache.get(&source).is_none());
+        let bundle = Decoder::new(&source)
+            .unwrap()
+            .load_animation_bundle()
+            .unwrap();
+        cache.insert(&source, &bundle).unwrap();
+        let cached = cache.get(&source).unwrap();
+        assert_eq!(cached.to_bytes(), bundle.to_bytes());
+
+        assert!(cache.get(&encode(1)).is_none());
+
+        fs::write(cache.entry_path(&source), b"ABDECOD").unwrap();
+        assert!(cache.get(&source).is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,330 @@
+use super::{
+    Animation, AnimationAtlas, CollisionBox, DecodedAnimationBundle, Frame, DEFAULT_FRAME_DURATION,
+};
+use anyhow::{bail, ensure, Result};
+use bevy::{
+    math::Vec2,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    render::texture::Image,
+    sprite::Rect,
+};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::{
+    io::{self, Cursor, Read, Write},
+    time::Duration,
+};
+
+/// Start of a decoded animation bundle, followed by a version byte. ANI files
+/// start with `CHFILEANI `, so the two can't be confused.
+const SIGNATURE: &[u8; 7] = b"ABDECOD";
+const VERSION: u8 = 1;
+
+/// Version of the ANI decoding. Cached bundles, see `AnimationCache`, are only
+/// used by the decoder version that created them. Bump it whenever `Decoder`
+/// or the atlas packer decode the same file differently, e.g. when TPAL or
+/// CBOX items, frame durations or the packing change.
+pub(super) const DECODER_VERSION: u32 = 1;
+
+/// Decoded animation bundles are stored in a binary format of their own, with
+/// the packed texture and the frame metadata, so they load without decoding
+/// the RLE frame images and packing them again. `AnimationAssetLoader` loads
+/// files in this format as well as ANI files.
+///
+/// All numbers are little endian, lists and strings start with their length.
+impl DecodedAnimationBundle {
+    /// Whether `bytes` hold a decoded bundle rather than an ANI file.
+    pub fn is_decoded(bytes: &[u8]) -> bool {
+        bytes.starts_with(SIGNATURE)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Vec::with_capacity(self.texture.data.len() + 1024);
+        self.write(&mut w).expect("writing to a Vec can't fail");
+        w
+    }
+
+    fn write(&self, w: &mut Vec<u8>) -> io::Result<()> {
+        w.write_all(SIGNATURE)?;
+        w.write_u8(VERSION)?;
+
+        write_bytes(w, &self.header)?;
+        w.write_u32::<LE>(self.palette.len() as u32)?;
+        for entry in self.palette.iter() {
+            w.write_all(entry)?;
+        }
+        w.write_u32::<LE>(self.transparency.len() as u32)?;
+        for transparent in self.transparency.iter() {
+            w.write_u8(*transparent as u8)?;
+        }
+        write_collision_box(w, self.collision_box)?;
+
+        let size = self.texture.texture_descriptor.size;
+        w.write_u32::<LE>(size.width)?;
+        w.write_u32::<LE>(size.height)?;
+        w.write_all(&self.texture.data)?;
+
+        w.write_u32::<LE>(self.rects.len() as u32)?;
+        for (rect, (hotspot_x, hotspot_y)) in self.rects.iter().zip(self.hotspots.iter()) {
+            for v in [rect.min.x, rect.min.y, rect.max.x, rect.max.y] {
+                w.write_u32::<LE>(v as u32)?;
+            }
+            w.write_u32::<LE>(*hotspot_x)?;
+            w.write_u32::<LE>(*hotspot_y)?;
+        }
+
+        w.write_u32::<LE>(self.animations.len() as u32)?;
+        for animation in self.animations.iter() {
+            write_bytes(w, animation.name.as_bytes())?;
+            w.write_u32::<LE>(animation.width)?;
+            w.write_u32::<LE>(animation.height)?;
+            w.write_u32::<LE>(animation.frames.len() as u32)?;
+            for frame in animation.frames.iter() {
+                w.write_u32::<LE>(frame.index as u32)?;
+                w.write_u32::<LE>(frame.width)?;
+                w.write_u32::<LE>(frame.height)?;
+                w.write_i32::<LE>(frame.offset_x)?;
+                w.write_i32::<LE>(frame.offset_y)?;
+                w.write_i32::<LE>(frame.hotspot_x)?;
+                w.write_i32::<LE>(frame.hotspot_y)?;
+                w.write_u32::<LE>(frame.duration.as_millis() as u32)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if !Self::is_decoded(bytes) {
+            bail!("Not a decoded animation bundle");
+        }
+        let mut r = Cursor::new(&bytes[SIGNATURE.len()..]);
+        let version = r.read_u8()?;
+        if version != VERSION {
+            bail!("Unsupported decoded animation bundle version {}", version);
+        }
+
+        let header = read_bytes(&mut r)?;
+        let palette = read_list(&mut r, 4, |r| {
+            let mut entry = [0; 4];
+            r.read_exact(&mut entry)?;
+            Ok(entry)
+        })?;
+        let transparency = read_list(&mut r, 1, |r| Ok(r.read_u8()? != 0))?;
+        let collision_box = read_collision_box(&mut r)?;
+
+        let width = r.read_u32::<LE>()?;
+        let height = r.read_u32::<LE>()?;
+        let size = 4 * width as u64 * height as u64;
+        ensure!(size <= remaining(&r), "Texture data is truncated");
+        let mut data = vec![0; size as usize];
+        r.read_exact(&mut data)?;
+        let texture = Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+
+        let frames = read_list(&mut r, 24, |r| {
+            let mut v = [0; 6];
+            r.read_u32_into::<LE>(&mut v)?;
+            let [min_x, min_y, max_x, max_y, hotspot_x, hotspot_y] = v;
+            ensure!(
+                min_x <= max_x && max_x <= width && min_y <= max_y && max_y <= height,
+                "Frame rect exceeds the texture"
+            );
+            let rect = Rect {
+                min: Vec2::new(min_x as f32, min_y as f32),
+                max: Vec2::new(max_x as f32, max_y as f32),
+            };
+            Ok((rect, (hotspot_x, hotspot_y)))
+        })?;
+        let (rects, hotspots): (Vec<Rect>, Vec<(u32, u32)>) = frames.into_iter().unzip();
+
+        let atlas = AnimationAtlas {
+            width,
+            height,
+            frame_count: rects.len(),
+            texture: Default::default(),
+        };
+        let animations = read_list(&mut r, 16, |r| {
+            let name = String::from_utf8(read_bytes(r)?)?;
+            let width = r.read_u32::<LE>()?;
+            let height = r.read_u32::<LE>()?;
+            let frames = read_list(r, 32, |r| {
+                let index = r.read_u32::<LE>()? as usize;
+                ensure!(index < rects.len(), "Frame index {} out of range", index);
+                Ok(Frame {
+                    index,
+                    width: r.read_u32::<LE>()?,
+                    height: r.read_u32::<LE>()?,
+                    offset_x: r.read_i32::<LE>()?,
+                    offset_y: r.read_i32::<LE>()?,
+                    hotspot_x: r.read_i32::<LE>()?,
+                    hotspot_y: r.read_i32::<LE>()?,
+                    // Same as in ANI files, a frame can't be shown for no time.
+                    duration: match r.read_u32::<LE>()? {
+                        0 => DEFAULT_FRAME_DURATION,
+                        ms => Duration::from_millis(ms as u64),
+                    },
+                })
+            })?;
+            ensure!(!frames.is_empty(), "Animation {:?} has no frames", name);
+            Ok(Animation {
+                name,
+                width,
+                height,
+                frames,
+                atlas: atlas.clone(),
+                collision_box,
+            })
+        })?;
+
+        ensure!(remaining(&r) == 0, "Trailing data after the animations");
+
+        Ok(DecodedAnimationBundle {
+            header,
+            palette,
+            transparency,
+            collision_box,
+            texture,
+            rects,
+            hotspots,
+            animations,
+        })
+    }
+}
+
+type Reader<'a> = Cursor<&'a [u8]>;
+
+fn remaining(r: &Reader) -> u64 {
+    r.get_ref().len() as u64 - r.position()
+}
+
+/// Reads a list of items that take at least `min_size` bytes each, the length
+/// is checked against the remaining data before anything is allocated.
+fn read_list<T>(
+    r: &mut Reader,
+    min_size: u64,
+    mut read: impl FnMut(&mut Reader) -> Result<T>,
+) -> Result<Vec<T>> {
+    let len = r.read_u32::<LE>()?;
+    ensure!(
+        len as u64 * min_size <= remaining(r),
+        "List of {} items is truncated",
+        len
+    );
+    (0..len).map(|_| read(r)).collect()
+}
+
+fn read_bytes(r: &mut Reader) -> Result<Vec<u8>> {
+    read_list(r, 1, |r| Ok(r.read_u8()?))
+}
+
+fn write_bytes(w: &mut Vec<u8>, bytes: &[u8]) -> io::Result<()> {
+    w.write_u32::<LE>(bytes.len() as u32)?;
+    w.write_all(bytes)
+}
+
+fn write_collision_box(w: &mut Vec<u8>, collision_box: Option<CollisionBox>) -> io::Result<()> {
+    match collision_box {
+        Some(b) => {
+            w.write_u8(1)?;
+            w.write_i32::<LE>(b.x)?;
+            w.write_i32::<LE>(b.y)?;
+            w.write_u32::<LE>(b.width)?;
+            w.write_u32::<LE>(b.height)
+        }
+        None => w.write_u8(0),
+    }
+}
+
+fn read_collision_box(r: &mut Reader) -> Result<Option<CollisionBox>> {
+    Ok(match r.read_u8()? {
+        0 => None,
+        _ => Some(CollisionBox {
+            x: r.read_i32::<LE>()?,
+            y: r.read_i32::<LE>()?,
+            width: r.read_u32::<LE>()?,
+            height: r.read_u32::<LE>()?,
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{Decoder, Encoder, EncoderFrame, EncoderSequence, EncoderSequenceFrame};
+
+    fn encode() -> Vec<u8> {
+        Encoder {
+            header: vec![7; 3],
+            collision_box: Some(CollisionBox {
+                x: -4,
+                y: -6,
+                width: 8,
+                height: 12,
+            }),
+            frames: vec![EncoderFrame {
+                name: "frame.png".into(),
+                width: 3,
+                height: 2,
+                hotspot_x: 1,
+                hotspot_y: 1,
+                pixels: (0..6).flat_map(|i| [i * 40, 8, 16, 255]).collect(),
+            }],
+            sequences: vec![EncoderSequence {
+                name: "stand".into(),
+                frames: vec![EncoderSequenceFrame {
+                    frame: 0,
+                    offset_x: 2,
+                    offset_y: -1,
+                    duration: Duration::from_millis(80),
+                }],
+            }],
+        }
+        .encode()
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let ani = encode();
+        let decoded = Decoder::new(&ani).unwrap().load_animation_bundle().unwrap();
+
+        let bytes = decoded.to_bytes();
+        assert!(DecodedAnimationBundle::is_decoded(&bytes));
+        assert!(!DecodedAnimationBundle::is_decoded(&ani));
+
+        let read = DecodedAnimationBundle::from_bytes(&bytes).unwrap();
+        assert_eq!(read.to_bytes(), bytes);
+        assert_eq!(read.frame_pixels(0), decoded.frame_pixels(0));
+        assert_eq!(read.animations[0].frames[0].offset_x, 2);
+
+        for len in 0..bytes.len() {
+            assert!(DecodedAnimationBundle::from_bytes(&bytes[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_animations_that_cant_play() {
+        let mut decoded = Decoder::new(&encode())
+            .unwrap()
+            .load_animation_bundle()
+            .unwrap();
+
+        decoded.animations[0].frames[0].duration = Duration::ZERO;
+        let read = DecodedAnimationBundle::from_bytes(&decoded.to_bytes()).unwrap();
+        assert_eq!(
+            read.animations[0].frames[0].duration,
+            DEFAULT_FRAME_DURATION
+        );
+
+        decoded.animations[0].frames.clear();
+        assert!(DecodedAnimationBundle::from_bytes(&decoded.to_bytes()).is_err());
+    }
+}
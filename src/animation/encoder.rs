@@ -0,0 +1,404 @@
+use super::CollisionBox;
+use anyhow::{bail, Result};
+use byteorder::{WriteBytesExt, LE};
+use std::time::Duration;
+
+/// Color key written for transparent pixels: magenta, which the sprites of
+/// the original game use for the same purpose.
+const TRANSPARENT: u16 = 0b0111_1100_0001_1111;
+
+/// Writes ANI files, the counterpart of `Decoder`.
+///
+/// Frame images are stored as 16bpp TGA-RLE CIMG items. Pixels with an alpha
+/// below 128 become transparent, colors are reduced to 5 bits per channel.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    /// Contents of the file HEAD, see `DecodedAnimationBundle::header`.
+    pub header: Vec<u8>,
+    pub collision_box: Option<CollisionBox>,
+    pub frames: Vec<EncoderFrame>,
+    pub sequences: Vec<EncoderSequence>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EncoderFrame {
+    /// Stored in the FNAM item, the decoder ignores it.
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub hotspot_x: u32,
+    pub hotspot_y: u32,
+    /// 32bit RGBA encoded pixels.
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EncoderSequence {
+    pub name: String,
+    pub frames: Vec<EncoderSequenceFrame>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EncoderSequenceFrame {
+    /// Index into `Encoder::frames`.
+    pub frame: usize,
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub duration: Duration,
+}
+
+impl Encoder {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut body = item(b"HEAD", &self.header);
+        body.extend(item(b"PAL ", &[]));
+        body.extend(item(b"TPAL", &[]));
+        body.extend(item(b"CBOX", &self.encode_collision_box()?));
+
+        for frame in self.frames.iter() {
+            body.extend(item(b"FRAM", &encode_frame(frame)?));
+        }
+
+        for sequence in self.sequences.iter() {
+            body.extend(item(b"SEQ ", &self.encode_sequence(sequence)?));
+        }
+
+        let mut data = b"CHFILEANI ".to_vec();
+        data.write_u32::<LE>(body.len() as u32)?;
+        data.write_u16::<LE>(0)?;
+        data.extend(body);
+        Ok(data)
+    }
+
+    fn encode_collision_box(&self) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        let b = self.collision_box.unwrap_or_default();
+        data.write_i16::<LE>(to_i16(b.x)?)?;
+        data.write_i16::<LE>(to_i16(b.y)?)?;
+        data.write_u16::<LE>(to_u16(b.width)?)?;
+        data.write_u16::<LE>(to_u16(b.height)?)?;
+        Ok(data)
+    }
+
+    fn encode_sequence(&self, sequence: &EncoderSequence) -> Result<Vec<u8>> {
+        if sequence.frames.is_empty() {
+            bail!("Sequence {} has no frames", sequence.name);
+        }
+        if sequence.name.contains('\0') {
+            bail!("Sequence name {:?} contains a nul byte", sequence.name);
+        }
+
+        let mut name = sequence.name.as_bytes().to_vec();
+        name.push(0);
+        let mut data = item(b"HEAD", &name);
+
+        for frame in sequence.frames.iter() {
+            if frame.frame >= self.frames.len() {
+                bail!(
+                    "Sequence {} refers to frame {}, there are only {}",
+                    sequence.name,
+                    frame.frame,
+                    self.frames.len()
+                );
+            }
+
+            // A duration of zero means the default frame rate.
+            let mut head = vec![];
+            head.write_u16::<LE>(frame.duration.as_millis().min(u16::MAX as u128) as u16)?;
+            head.write_u16::<LE>(0)?;
+
+            let mut fram = vec![];
+            fram.write_u16::<LE>(0)?;
+            fram.write_u16::<LE>(to_u16(frame.frame as u32)?)?;
+            fram.write_i16::<LE>(to_i16(frame.offset_x)?)?;
+            fram.write_i16::<LE>(to_i16(frame.offset_y)?)?;
+            fram.write_u32::<LE>(0)?;
+
+            let mut stat = item(b"HEAD", &head);
+            stat.extend(item(b"FRAM", &fram));
+            data.extend(item(b"STAT", &stat));
+        }
+
+        Ok(data)
+    }
+}
+
+/// A frame is a HEAD, the FNAM with the name of the image and the CIMG.
+fn encode_frame(frame: &EncoderFrame) -> Result<Vec<u8>> {
+    let width = to_u16(frame.width)?;
+    let height = to_u16(frame.height)?;
+    let pixel_count = width as usize * height as usize;
+    if frame.pixels.len() != 4 * pixel_count {
+        bail!(
+            "Frame {} has {} bytes of pixels, expected {}",
+            frame.name,
+            frame.pixels.len(),
+            4 * pixel_count
+        );
+    }
+
+    let pixels: Vec<u16> = frame
+        .pixels
+        .chunks_exact(4)
+        .map(|p| {
+            if p[3] < 128 {
+                TRANSPARENT
+            } else {
+                let color =
+                    ((p[0] as u16 >> 3) << 10) | ((p[1] as u16 >> 3) << 5) | (p[2] as u16 >> 3);
+                // Opaque pixels must not collide with the color key.
+                if color == TRANSPARENT {
+                    color - 1
+                } else {
+                    color
+                }
+            }
+        })
+        .collect();
+    let compressed = encode_rle(&pixels)?;
+
+    let mut cimg = vec![];
+    // 16bpp image type, without a palette.
+    cimg.write_u16::<LE>(0x0004)?;
+    cimg.write_u16::<LE>(0)?;
+    cimg.write_u32::<LE>(24)?;
+    cimg.write_u32::<LE>(0)?;
+    cimg.write_u16::<LE>(width)?;
+    cimg.write_u16::<LE>(height)?;
+    cimg.write_u16::<LE>(to_u16(frame.hotspot_x)?)?;
+    cimg.write_u16::<LE>(to_u16(frame.hotspot_y)?)?;
+    cimg.write_u16::<LE>(TRANSPARENT)?;
+    cimg.write_u16::<LE>(0)?;
+    cimg.write_u16::<LE>(0)?;
+    cimg.write_u16::<LE>(0)?;
+    // Both sizes include the 12 bytes of this header.
+    cimg.write_u32::<LE>(compressed.len() as u32 + 12)?;
+    cimg.write_u32::<LE>(2 * pixel_count as u32 + 12)?;
+    cimg.extend(compressed);
+
+    let mut name = frame.name.as_bytes().to_vec();
+    name.push(0);
+
+    let mut data = item(b"HEAD", &[0; 4]);
+    data.extend(item(b"FNAM", &name));
+    data.extend(item(b"CIMG", &cimg));
+    Ok(data)
+}
+
+/// Encodes pixels with TGA run length encoding, see `TgaRleIterator`. Runs of
+/// at least two equal pixels become RLE packets, everything in between raw
+/// packets. Packets hold up to 128 pixels.
+fn encode_rle(pixels: &[u16]) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    let mut raw: Vec<u16> = vec![];
+
+    fn flush_raw(data: &mut Vec<u8>, raw: &mut Vec<u16>) -> Result<()> {
+        for packet in raw.chunks(128) {
+            data.write_u8(packet.len() as u8 - 1)?;
+            for pixel in packet {
+                data.write_u16::<LE>(*pixel)?;
+            }
+        }
+        raw.clear();
+        Ok(())
+    }
+
+    let mut i = 0;
+    while i < pixels.len() {
+        let run = pixels[i..]
+            .iter()
+            .take(128)
+            .take_while(|p| **p == pixels[i])
+            .count();
+
+        if run >= 2 {
+            flush_raw(&mut data, &mut raw)?;
+            data.write_u8(0b1000_0000 | (run as u8 - 1))?;
+            data.write_u16::<LE>(pixels[i])?;
+        } else {
+            raw.push(pixels[i]);
+        }
+        i += run;
+    }
+    flush_raw(&mut data, &mut raw)?;
+
+    Ok(data)
+}
+
+fn item(signature: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut data = signature.to_vec();
+    data.extend((body.len() as u32).to_le_bytes());
+    data.extend(0u16.to_le_bytes());
+    data.extend(body);
+    data
+}
+
+fn to_u16(v: u32) -> Result<u16> {
+    match u16::try_from(v) {
+        Ok(v) => Ok(v),
+        Err(_) => bail!("{} does not fit into 16 bits", v),
+    }
+}
+
+fn to_i16(v: i32) -> Result<i16> {
+    match i16::try_from(v) {
+        Ok(v) => Ok(v),
+        Err(_) => bail!("{} does not fit into 16 bits", v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{Decoder, DEFAULT_FRAME_DURATION};
+
+    fn frame(
+        name: &str,
+        width: u32,
+        height: u32,
+        pixel: impl Fn(u32, u32) -> [u8; 4],
+    ) -> EncoderFrame {
+        EncoderFrame {
+            name: name.into(),
+            width,
+            height,
+            hotspot_x: width / 2,
+            hotspot_y: height - 1,
+            pixels: (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .flat_map(|(x, y)| pixel(x, y))
+                .collect(),
+        }
+    }
+
+    fn encoder() -> Encoder {
+        Encoder {
+            header: vec![1, 2, 3, 4],
+            collision_box: Some(CollisionBox {
+                x: -8,
+                y: -20,
+                width: 16,
+                height: 22,
+            }),
+            frames: vec![
+                // Long runs, raw stretches and transparency.
+                frame("first.png", 200, 3, |x, y| match (x / 50, y) {
+                    (0, _) => [0, 0, 0, 0],
+                    (1, 1) => [(x as u8) << 3, 8, 248, 255],
+                    _ => [248, 128, 0, 255],
+                }),
+                frame("second.png", 5, 7, |x, y| {
+                    [(x * 40) as u8, (y * 32) as u8, 16, 255]
+                }),
+            ],
+            sequences: vec![
+                EncoderSequence {
+                    name: "walk north".into(),
+                    frames: vec![
+                        EncoderSequenceFrame {
+                            frame: 1,
+                            offset_x: -3,
+                            offset_y: 4,
+                            duration: Duration::from_millis(100),
+                        },
+                        EncoderSequenceFrame {
+                            frame: 0,
+                            offset_x: 0,
+                            offset_y: 0,
+                            duration: DEFAULT_FRAME_DURATION,
+                        },
+                    ],
+                },
+                EncoderSequence {
+                    name: "stand".into(),
+                    frames: vec![EncoderSequenceFrame {
+                        frame: 0,
+                        offset_x: 7,
+                        offset_y: -7,
+                        duration: Duration::from_millis(250),
+                    }],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let encoder = encoder();
+        let data = encoder.encode().unwrap();
+        let decoded = Decoder::new(&data)
+            .unwrap()
+            .load_animation_bundle()
+            .unwrap();
+
+        assert_eq!(decoded.header, encoder.header);
+        assert_eq!(decoded.collision_box, encoder.collision_box);
+
+        assert_eq!(decoded.rects.len(), encoder.frames.len());
+        for (i, frame) in encoder.frames.iter().enumerate() {
+            assert_eq!(
+                decoded.frame_pixels(i).unwrap(),
+                frame.pixels,
+                "frame {}",
+                i
+            );
+            assert_eq!(decoded.hotspots[i], (frame.hotspot_x, frame.hotspot_y));
+        }
+
+        assert_eq!(decoded.animations.len(), encoder.sequences.len());
+        for (animation, sequence) in decoded.animations.iter().zip(encoder.sequences.iter()) {
+            assert_eq!(animation.name, sequence.name);
+            assert_eq!(animation.frames.len(), sequence.frames.len());
+            for (frame, expected) in animation.frames.iter().zip(sequence.frames.iter()) {
+                let image = &encoder.frames[expected.frame];
+                assert_eq!(frame.index, expected.frame);
+                assert_eq!(
+                    (frame.offset_x, frame.offset_y),
+                    (expected.offset_x, expected.offset_y)
+                );
+                assert_eq!((frame.width, frame.height), (image.width, image.height));
+                assert_eq!(frame.duration, expected.duration);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_reencodes_identically() {
+        let data = encoder().encode().unwrap();
+        let decoded = Decoder::new(&data)
+            .unwrap()
+            .load_animation_bundle()
+            .unwrap();
+
+        let reencoded = Encoder {
+            header: decoded.header.clone(),
+            collision_box: decoded.collision_box,
+            frames: (0..decoded.rects.len())
+                .map(|i| {
+                    let rect = decoded.rects[i];
+                    EncoderFrame {
+                        name: encoder().frames[i].name.clone(),
+                        width: (rect.max.x - rect.min.x) as u32,
+                        height: (rect.max.y - rect.min.y) as u32,
+                        hotspot_x: decoded.hotspots[i].0,
+                        hotspot_y: decoded.hotspots[i].1,
+                        pixels: decoded.frame_pixels(i).unwrap(),
+                    }
+                })
+                .collect(),
+            sequences: encoder().sequences,
+        };
+
+        assert_eq!(reencoded.encode().unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let mut encoder = encoder();
+        encoder.sequences[0].frames[0].frame = 5;
+        assert!(encoder.encode().is_err());
+
+        let mut encoder = self::encoder();
+        encoder.frames[0].pixels.pop();
+        assert!(encoder.encode().is_err());
+    }
+}
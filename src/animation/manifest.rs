@@ -0,0 +1,73 @@
+use super::CollisionBox;
+use serde::{Deserialize, Serialize};
+
+/// Describes the frames and sequences of an ANI file next to its frame
+/// images, as written by `ani_export` and read by `ani_import`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub source: String,
+    /// Contents of the file HEAD. Their meaning is unknown, they are kept so
+    /// a file can be re-encoded unchanged.
+    #[serde(default)]
+    pub header: Vec<u8>,
+    pub collision_box: Option<ManifestCollisionBox>,
+    pub frames: Vec<ManifestFrame>,
+    pub sequences: Vec<ManifestSequence>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ManifestCollisionBox {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestFrame {
+    pub index: usize,
+    /// Image file, relative to the manifest. `None` for frames without
+    /// pixels, PNG can't store those.
+    pub file: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub hotspot_x: u32,
+    pub hotspot_y: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestSequence {
+    pub name: String,
+    pub frames: Vec<ManifestSequenceFrame>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestSequenceFrame {
+    /// Index into the frames of the manifest.
+    pub frame: usize,
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub duration_ms: u64,
+}
+
+impl From<CollisionBox> for ManifestCollisionBox {
+    fn from(b: CollisionBox) -> Self {
+        ManifestCollisionBox {
+            x: b.x,
+            y: b.y,
+            width: b.width,
+            height: b.height,
+        }
+    }
+}
+
+impl From<ManifestCollisionBox> for CollisionBox {
+    fn from(b: ManifestCollisionBox) -> Self {
+        CollisionBox {
+            x: b.x,
+            y: b.y,
+            width: b.width,
+            height: b.height,
+        }
+    }
+}
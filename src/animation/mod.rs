@@ -1,5 +1,13 @@
 mod animated_sprite;
 mod asset_loader;
+mod cache;
+mod decoded;
+mod encoder;
+mod manifest;
+mod packer;
 
 pub use animated_sprite::*;
 pub use asset_loader::*;
+pub use cache::AnimationCache;
+pub use encoder::*;
+pub use manifest::*;
@@ -0,0 +1,153 @@
+/// Transparent pixels left between packed rects, so filtering at the edge of
+/// a frame doesn't pick up its neighbour.
+const SPACING: u32 = 1;
+
+/// All frame images of a file are packed into one texture, which may be at
+/// most this wide and high.
+pub const MAX_ATLAS_SIZE: u32 = 4096;
+
+/// Position of a packed rect in the atlas, in pixels from the top left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Atlas size and packed rects.
+pub type Packing = ((u32, u32), Vec<PackedRect>);
+
+/// Packs rects of the given sizes into shelves: the rects are sorted by
+/// height and placed left to right, a new shelf is started once a row is
+/// full. The atlas width is the smallest power of two that makes the result
+/// roughly square, so no dimension grows out of GPU texture limits.
+///
+/// Returns the atlas size and the rects in the order of `sizes`, or the atlas
+/// size as error if it exceeds `MAX_ATLAS_SIZE`.
+pub fn pack(sizes: &[(u32, u32)]) -> Result<Packing, (u32, u32)> {
+    let area: u64 = sizes
+        .iter()
+        .map(|(w, h)| (*w + SPACING) as u64 * (*h + SPACING) as u64)
+        .sum();
+    let widest = sizes.iter().map(|(w, _)| *w).max().unwrap_or(0);
+    let atlas_width = ((area as f64).sqrt().ceil() as u32)
+        .max(widest)
+        .max(1)
+        .next_power_of_two();
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| (std::cmp::Reverse(sizes[i].1), std::cmp::Reverse(sizes[i].0)));
+
+    let mut rects = vec![
+        PackedRect {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        };
+        sizes.len()
+    ];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    let mut used_width = 0;
+
+    for i in order {
+        let (width, height) = sizes[i];
+        if x > 0 && x + width > atlas_width {
+            x = 0;
+            y += shelf_height + SPACING;
+            shelf_height = 0;
+        }
+
+        rects[i] = PackedRect {
+            x,
+            y,
+            width,
+            height,
+        };
+        used_width = used_width.max(x + width);
+        shelf_height = shelf_height.max(height);
+        x += width + SPACING;
+    }
+
+    let size = (used_width.max(1), (y + shelf_height).max(1));
+    if size.0 > MAX_ATLAS_SIZE || size.1 > MAX_ATLAS_SIZE {
+        return Err(size);
+    }
+    Ok((size, rects))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlap(a: &PackedRect, b: &PackedRect) -> bool {
+        a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    /// `a` grown by the spacing on its right and bottom edge.
+    fn with_spacing(a: &PackedRect) -> PackedRect {
+        PackedRect {
+            width: a.width + SPACING,
+            height: a.height + SPACING,
+            ..*a
+        }
+    }
+
+    #[test]
+    fn rects_are_spaced_and_fit() {
+        let sizes: Vec<(u32, u32)> = (1..60).map(|i| (i * 7 % 45 + 1, i * 13 % 38 + 1)).collect();
+        let ((width, height), rects) = pack(&sizes).unwrap();
+
+        // The shelves are no wider than the power of two the packer picks.
+        let area: u32 = sizes
+            .iter()
+            .map(|(w, h)| (w + SPACING) * (h + SPACING))
+            .sum();
+        let widest = sizes.iter().map(|(w, _)| *w).max().unwrap();
+        let atlas_width = ((area as f64).sqrt().ceil() as u32)
+            .max(widest)
+            .next_power_of_two();
+        assert!(width <= atlas_width);
+        for (rect, (w, h)) in rects.iter().zip(sizes.iter()) {
+            assert_eq!((rect.width, rect.height), (*w, *h));
+            assert!(rect.x + rect.width <= width && rect.y + rect.height <= height);
+        }
+
+        for (i, a) in rects.iter().enumerate() {
+            for b in rects[i + 1..].iter() {
+                assert!(!overlap(a, b), "{:?} overlaps {:?}", a, b);
+                assert!(
+                    !overlap(&with_spacing(a), b) && !overlap(a, &with_spacing(b)),
+                    "{:?} and {:?} are not spaced",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn width_is_a_power_of_two_that_fits_the_widest_rect() {
+        let ((width, _), _) = pack(&[(300, 10), (20, 20), (20, 20)]).unwrap();
+        assert!((300..=512).contains(&width));
+        assert_eq!(pack(&[]).unwrap().0, (1, 1));
+    }
+
+    #[test]
+    fn fails_above_max_atlas_size() {
+        assert_eq!(
+            pack(&[(MAX_ATLAS_SIZE + 1, 1)]),
+            Err((MAX_ATLAS_SIZE + 1, 1))
+        );
+        assert_eq!(
+            pack(&[(1, MAX_ATLAS_SIZE + 1)]).map(|_| ()),
+            Err((1, MAX_ATLAS_SIZE + 1))
+        );
+        assert!(pack(&[(MAX_ATLAS_SIZE, MAX_ATLAS_SIZE)]).is_ok());
+
+        let sizes = vec![(1000, 1000); 20];
+        let (width, height) = pack(&sizes).unwrap_err();
+        assert!(width > MAX_ATLAS_SIZE || height > MAX_ATLAS_SIZE);
+    }
+}
@@ -0,0 +1,53 @@
+mod case_insensitive;
+mod pack;
+
+pub use case_insensitive::CaseInsensitiveAssetIo;
+pub use pack::{Pack, PackAssetIo, PackWriter};
+
+use bevy::{
+    asset::{
+        create_platform_default_asset_io, AssetIo, AssetServer, AssetServerSettings, FileAssetIo,
+    },
+    prelude::*,
+    tasks::IoTaskPool,
+};
+
+/// Name of the pack file in the asset folder, see `PackWriter`. When it
+/// exists, assets are read from it.
+pub const PACK_FILE: &str = "data.pak";
+
+/// Reads assets through `CaseInsensitiveAssetIo`, so the data files are found
+/// however a copy of the original data is cased. When the asset folder holds
+/// a `PACK_FILE`, assets are served from it, loose files are the fallback.
+///
+/// It replaces the `AssetServer`, so it must be added before `AssetPlugin`:
+///
+/// ```ignore
+/// app.add_plugins_with(DefaultPlugins, |group| {
+///     group.add_before::<AssetPlugin, _>(AssetIoPlugin)
+/// });
+/// ```
+#[derive(Default)]
+pub struct AssetIoPlugin;
+
+impl Plugin for AssetIoPlugin {
+    fn build(&self, app: &mut App) {
+        let mut asset_io: Box<dyn AssetIo> = Box::new(CaseInsensitiveAssetIo::new(
+            create_platform_default_asset_io(app),
+        ));
+
+        let asset_folder = &app.world.resource::<AssetServerSettings>().asset_folder;
+        let pack_path = FileAssetIo::get_root_path()
+            .join(asset_folder)
+            .join(PACK_FILE);
+        if pack_path.is_file() {
+            match Pack::open(&pack_path) {
+                Ok(pack) => asset_io = Box::new(PackAssetIo::new(pack, asset_io)),
+                Err(err) => error!("Ignoring {:?}: {:#}", pack_path, err),
+            }
+        }
+
+        let task_pool = app.world.resource::<IoTaskPool>().0.clone();
+        app.insert_resource(AssetServer::with_boxed_io(asset_io, task_pool));
+    }
+}
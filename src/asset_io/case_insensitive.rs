@@ -0,0 +1,128 @@
+use bevy::{
+    asset::{AssetIo, AssetIoError},
+    utils::BoxedFuture,
+};
+use std::{
+    ffi::OsStr,
+    path::{Component, Path, PathBuf},
+};
+
+/// Wraps an `AssetIo` and looks up paths that don't exist as given ignoring
+/// case. The original data mixes upper and lower case file names, e.g.
+/// `dis2a.rss` next to `BMDROP2.RSS`, and copies from case-insensitive
+/// filesystems may have changed them.
+///
+/// Exact matches are preferred, a path is only resolved when it isn't found.
+pub struct CaseInsensitiveAssetIo {
+    inner: Box<dyn AssetIo>,
+}
+
+impl CaseInsensitiveAssetIo {
+    pub fn new(inner: Box<dyn AssetIo>) -> Self {
+        CaseInsensitiveAssetIo { inner }
+    }
+
+    /// Finds the path as it is cased on disk, one component at a time.
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let mut resolved = PathBuf::new();
+
+        for component in path.components() {
+            let name = match component {
+                Component::Normal(name) => name,
+                Component::CurDir => continue,
+                // Only paths below the asset root are resolved.
+                _ => return None,
+            };
+
+            let entries: Vec<PathBuf> = self.inner.read_directory(&resolved).ok()?.collect();
+            let file_name = |entry: &PathBuf| entry.file_name().map(OsStr::to_owned);
+            resolved = entries
+                .iter()
+                .find(|entry| file_name(entry).as_deref() == Some(name))
+                .or_else(|| {
+                    let name = name.to_string_lossy().to_lowercase();
+                    entries.iter().find(|entry| {
+                        file_name(entry).map(|n| n.to_string_lossy().to_lowercase())
+                            == Some(name.clone())
+                    })
+                })?
+                .clone();
+        }
+
+        Some(resolved)
+    }
+}
+
+impl AssetIo for CaseInsensitiveAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        Box::pin(async move {
+            match self.inner.load_path(path).await {
+                Err(AssetIoError::NotFound(not_found)) => match self.resolve(path) {
+                    Some(resolved) => self.inner.load_path(&resolved).await,
+                    None => Err(AssetIoError::NotFound(not_found)),
+                },
+                result => result,
+            }
+        })
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        if self.inner.is_directory(path) {
+            return self.inner.read_directory(path);
+        }
+        match self.resolve(path) {
+            Some(resolved) => self.inner.read_directory(&resolved),
+            None => self.inner.read_directory(path),
+        }
+    }
+
+    fn is_directory(&self, path: &Path) -> bool {
+        self.inner.is_directory(path)
+            || matches!(self.resolve(path), Some(resolved) if self.inner.is_directory(&resolved))
+    }
+
+    fn watch_path_for_changes(&self, path: &Path) -> Result<(), AssetIoError> {
+        let resolved = self.resolve(path);
+        self.inner
+            .watch_path_for_changes(resolved.as_deref().unwrap_or(path))
+    }
+
+    fn watch_for_changes(&self) -> Result<(), AssetIoError> {
+        self.inner.watch_for_changes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::FileAssetIo;
+    use std::fs;
+
+    #[test]
+    fn resolves_any_case() {
+        let root = std::env::temp_dir().join(format!("case_insensitive_{}", std::process::id()));
+        fs::create_dir_all(root.join("data/SOUND")).unwrap();
+        fs::write(root.join("data/SOUND/dis2a.rss"), b"").unwrap();
+        fs::write(root.join("data/SOUND/BMDROP2.RSS"), b"").unwrap();
+
+        let asset_io = CaseInsensitiveAssetIo::new(Box::new(FileAssetIo::new(&root, false)));
+        let resolve = |path: &str| asset_io.resolve(Path::new(path));
+
+        assert_eq!(
+            resolve("data/SOUND/DIS2A.RSS"),
+            Some(PathBuf::from("data/SOUND/dis2a.rss"))
+        );
+        assert_eq!(
+            resolve("DATA/sound/bmdrop2.rss"),
+            Some(PathBuf::from("data/SOUND/BMDROP2.RSS"))
+        );
+        assert_eq!(resolve("data/SOUND/missing.rss"), None);
+        assert_eq!(resolve("../data/SOUND/BMDROP2.RSS"), None);
+        assert!(asset_io.is_directory(Path::new("DATA/Sound")));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,314 @@
+use anyhow::{bail, Context, Result};
+use bevy::{
+    asset::{AssetIo, AssetIoError},
+    utils::BoxedFuture,
+};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+/// Start of a pack file, followed by a version byte.
+const SIGNATURE: &[u8; 7] = b"ABPACK\0";
+const VERSION: u8 = 1;
+
+/// An archive of asset files, so the game can be shipped as one file instead
+/// of thousands of loose data files.
+///
+/// The layout is a header with the number of files, an index with the path,
+/// offset and size of every file, followed by the file contents. Numbers are
+/// little endian. Paths are relative to the asset folder, with `/` between
+/// components, e.g. `data/ANI/BOMBS.ANI`. They are looked up ignoring case,
+/// like `CaseInsensitiveAssetIo` does.
+#[derive(Debug)]
+pub struct Pack {
+    path: PathBuf,
+    /// Entries by their lower case path.
+    entries: HashMap<String, PackEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct PackEntry {
+    /// Normalized path, see `normalize`.
+    path: String,
+    offset: u64,
+    size: u64,
+}
+
+/// Asset path with `/` between components. `None` for paths that point
+/// outside of the asset folder.
+fn normalize(path: &Path) -> Option<String> {
+    let mut components = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => components.push(name.to_str()?),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(components.join("/"))
+}
+
+/// Key of a path in the index. Only ASCII is case folded, so the key and the
+/// path have the same length.
+fn key(path: &Path) -> Option<String> {
+    normalize(path).map(|path| path.to_ascii_lowercase())
+}
+
+impl Pack {
+    /// Reads the index of a pack file, the contents are read on demand.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Pack> {
+        let path = path.into();
+        let file = File::open(&path).with_context(|| format!("Opening {:?}", path))?;
+        let file_size = file.metadata()?.len();
+        let mut r = BufReader::new(file);
+
+        let mut signature = [0; 7];
+        r.read_exact(&mut signature)?;
+        if signature != *SIGNATURE {
+            bail!("{:?} is not a pack file", path);
+        }
+        let version = r.read_u8()?;
+        if version != VERSION {
+            bail!("Unsupported pack version {} in {:?}", version, path);
+        }
+
+        let count = r.read_u32::<LE>()?;
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            let len = r.read_u16::<LE>()?;
+            let mut name = vec![0; len as usize];
+            r.read_exact(&mut name)?;
+            let name = String::from_utf8(name).context("Pack path is not valid UTF-8")?;
+            let offset = r.read_u64::<LE>()?;
+            let size = r.read_u64::<LE>()?;
+
+            match offset.checked_add(size) {
+                Some(end) if end <= file_size => {}
+                _ => bail!("{} exceeds the end of {:?}", name, path),
+            }
+            let name = match normalize(Path::new(&name)) {
+                Some(name) => name,
+                None => bail!("Invalid path {} in {:?}", name, path),
+            };
+            let key = name.to_ascii_lowercase();
+            let entry = PackEntry {
+                path: name,
+                offset,
+                size,
+            };
+            if entries.insert(key, entry).is_some() {
+                bail!("Duplicate path in {:?}", path);
+            }
+        }
+
+        Ok(Pack { path, entries })
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.entry(path).is_some()
+    }
+
+    fn entry(&self, path: &Path) -> Option<&PackEntry> {
+        self.entries.get(&key(path)?)
+    }
+
+    /// Contents of the file at `path`, `None` if the pack doesn't hold it.
+    pub fn read(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        let entry = match self.entry(path) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut bytes = vec![0; entry.size as usize];
+        file.read_exact(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    /// Paths of the files and directories directly inside `dir`.
+    fn read_directory(&self, dir: &Path) -> Option<BTreeSet<PathBuf>> {
+        let dir = key(dir)?;
+        let prefix = if dir.is_empty() { dir } else { dir + "/" };
+
+        let children: BTreeSet<PathBuf> = self
+            .entries
+            .iter()
+            .filter_map(|(key, entry)| {
+                let rest = key.strip_prefix(&prefix)?;
+                // Keep the case of the stored path.
+                let len = prefix.len() + rest.find('/').unwrap_or(rest.len());
+                Some(PathBuf::from(&entry.path[..len]))
+            })
+            .collect();
+
+        if children.is_empty() {
+            None
+        } else {
+            Some(children)
+        }
+    }
+
+    fn is_directory(&self, path: &Path) -> bool {
+        !self.contains(path) && self.read_directory(path).is_some()
+    }
+}
+
+/// Writes a pack file, see `Pack`.
+#[derive(Debug, Default)]
+pub struct PackWriter {
+    files: Vec<(String, Vec<u8>)>,
+    keys: HashSet<String>,
+}
+
+impl PackWriter {
+    /// Adds a file under its asset path, e.g. `data/ANI/BOMBS.ANI`.
+    pub fn add(&mut self, path: &str, bytes: Vec<u8>) -> Result<()> {
+        let path = match normalize(Path::new(path)) {
+            Some(normalized) if !normalized.is_empty() => normalized,
+            _ => bail!("Invalid pack path {:?}", path),
+        };
+        if path.len() > u16::MAX as usize {
+            bail!("Pack path {:?} is too long", path);
+        }
+        if !self.keys.insert(path.to_ascii_lowercase()) {
+            bail!("{:?} is already in the pack", path);
+        }
+
+        self.files.push((path, bytes));
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("Creating {:?}", path))?;
+        let mut w = BufWriter::new(file);
+
+        w.write_all(SIGNATURE)?;
+        w.write_u8(VERSION)?;
+        w.write_u32::<LE>(self.files.len() as u32)?;
+
+        let index_size: u64 = self
+            .files
+            .iter()
+            .map(|(path, _)| 2 + path.len() as u64 + 16)
+            .sum();
+        let mut offset = SIGNATURE.len() as u64 + 1 + 4 + index_size;
+        for (path, bytes) in self.files.iter() {
+            w.write_u16::<LE>(path.len() as u16)?;
+            w.write_all(path.as_bytes())?;
+            w.write_u64::<LE>(offset)?;
+            w.write_u64::<LE>(bytes.len() as u64)?;
+            offset += bytes.len() as u64;
+        }
+
+        for (_, bytes) in self.files.iter() {
+            w.write_all(bytes)?;
+        }
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Serves assets from a `Pack`, files that are not in it are read from
+/// `fallback`. Packed files take precedence, loose files can add to the pack
+/// but not replace its files.
+pub struct PackAssetIo {
+    pack: Pack,
+    fallback: Box<dyn AssetIo>,
+}
+
+impl PackAssetIo {
+    pub fn new(pack: Pack, fallback: Box<dyn AssetIo>) -> Self {
+        PackAssetIo { pack, fallback }
+    }
+}
+
+impl AssetIo for PackAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        Box::pin(async move {
+            match self.pack.read(path)? {
+                Some(bytes) => Ok(bytes),
+                None => self.fallback.load_path(path).await,
+            }
+        })
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        let mut children = self.pack.read_directory(path).unwrap_or_default();
+        if self.fallback.is_directory(path) {
+            children.extend(self.fallback.read_directory(path)?);
+        } else if children.is_empty() {
+            return Err(AssetIoError::NotFound(path.to_owned()));
+        }
+        Ok(Box::new(children.into_iter()))
+    }
+
+    fn is_directory(&self, path: &Path) -> bool {
+        self.pack.is_directory(path) || self.fallback.is_directory(path)
+    }
+
+    fn watch_path_for_changes(&self, path: &Path) -> Result<(), AssetIoError> {
+        self.fallback.watch_path_for_changes(path)
+    }
+
+    fn watch_for_changes(&self) -> Result<(), AssetIoError> {
+        self.fallback.watch_for_changes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join(format!("pack_{}.pak", std::process::id()));
+
+        let mut writer = PackWriter::default();
+        writer.add("data/ANI/BOMBS.ANI", vec![1, 2, 3]).unwrap();
+        writer.add("data/SOUND/dis2a.rss", vec![]).unwrap();
+        writer
+            .add("fonts/FiraSans-Bold.ttf", vec![4; 1000])
+            .unwrap();
+        assert!(writer.add("DATA/ani/bombs.ani", vec![]).is_err());
+        assert!(writer.add("../outside", vec![]).is_err());
+        writer.write(&path).unwrap();
+
+        let pack = Pack::open(&path).unwrap();
+        let read = |p: &str| pack.read(Path::new(p)).unwrap();
+        assert_eq!(read("data/ANI/BOMBS.ANI"), Some(vec![1, 2, 3]));
+        assert_eq!(read("DATA/SOUND/DIS2A.RSS"), Some(vec![]));
+        assert_eq!(read("fonts/FiraSans-Bold.ttf"), Some(vec![4; 1000]));
+        assert_eq!(read("data/ANI/WALK.ANI"), None);
+
+        let dir = |p: &str| pack.read_directory(Path::new(p));
+        assert_eq!(
+            dir(""),
+            Some(BTreeSet::from(["data".into(), "fonts".into()]))
+        );
+        assert_eq!(
+            dir("data/ani"),
+            Some(BTreeSet::from(["data/ANI/BOMBS.ANI".into()]))
+        );
+        assert!(pack.is_directory(Path::new("data/SOUND")));
+        assert!(!pack.is_directory(Path::new("data/ANI/BOMBS.ANI")));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,21 +1,36 @@
 mod color_palette;
 mod color_remap_table;
+mod file_list;
+mod pcx_encoder;
 mod pcx_image;
 mod raw_sound;
 mod scheme;
+mod sidecar;
 
-use crate::animation::{Animation, AnimationAssetLoader, AnimationBundle};
+use crate::animation::{Animation, AnimationAssetLoader, AnimationBundle, AnimationCache};
 pub use color_palette::ColorPalette;
 use color_palette::ColorPaletteAssetLoader;
 pub use color_remap_table::ColorRemapTable;
 use color_remap_table::ColorRemapTableAssetLoader;
+pub use file_list::{parse_file_list, FileList};
+use file_list::FileListAssetLoader;
+pub use pcx_encoder::PcxEncoder;
+pub use pcx_image::{IndexedImage, PcxDecoder, PcxInfo, PcxSettings};
 use pcx_image::PcxImageAssetLoader;
+pub use raw_sound::{
+    parse_format, prepend_wav_header, RawSound, RawSoundFormat, RSS_SIDECAR,
+};
 use raw_sound::RawSoundAssetLoader;
 use scheme::SchemeAssetLoader;
 
-pub use scheme::{Cell, Grid, Powerup, PowerupInfo, Scheme};
+pub use scheme::{parse_scheme, Cell, Grid, Powerup, PowerupInfo, Scheme};
+pub use sidecar::sidecar_paths;
 
-use bevy::prelude::*;
+use bevy::{
+    audio::{play_queued_audio_system, AudioOutput},
+    ecs::system::IntoExclusiveSystem,
+    prelude::*,
+};
 
 /// Adds support for loading custom assets.
 #[derive(Default)]
@@ -25,14 +40,26 @@ impl Plugin for CustomAssetLoaders {
     fn build(&self, app: &mut App) {
         app.add_asset::<AnimationBundle>()
             .add_asset::<Animation>()
+            .init_resource::<AnimationCache>()
             .init_asset_loader::<AnimationAssetLoader>()
+            .add_asset::<PcxInfo>()
+            .add_asset::<IndexedImage>()
             .init_asset_loader::<PcxImageAssetLoader>()
+            .add_asset::<RawSound>()
+            .init_non_send_resource::<AudioOutput<RawSound>>()
+            .init_resource::<Audio<RawSound>>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                play_queued_audio_system::<RawSound>.exclusive_system(),
+            )
             .init_asset_loader::<RawSoundAssetLoader>()
             .add_asset::<ColorPalette>()
             .init_asset_loader::<ColorPaletteAssetLoader>()
             .add_asset::<ColorRemapTable>()
             .init_asset_loader::<ColorRemapTableAssetLoader>()
             .add_asset::<Scheme>()
-            .init_asset_loader::<SchemeAssetLoader>();
+            .init_asset_loader::<SchemeAssetLoader>()
+            .add_asset::<FileList>()
+            .init_asset_loader::<FileListAssetLoader>();
     }
 }
@@ -8,16 +8,20 @@ use bevy::{
 #[derive(Default)]
 pub struct ColorPaletteAssetLoader;
 
-const PALETTE_SIZE: usize = 256 * 3 + 32768; // 256 RGB colors + 2^15 mapping.
+const COLORS_SIZE: usize = 256 * 3;
+const PALETTE_SIZE: usize = COLORS_SIZE + 32768; // 256 RGB colors + 2^15 mapping.
 
 #[derive(Default, TypeUuid)]
 #[uuid = "398f6778-81f8-4293-a3b9-3b72ba4b5b55"]
 pub struct ColorPalette {
     data: Vec<u8>,
+    /// VGA palettes store 6 bit color values (0-63), see
+    /// https://falloutmods.fandom.com/wiki/PAL_File_Format
+    six_bit: bool,
 }
 
 impl ColorPalette {
-    fn new(bytes: &[u8]) -> Result<ColorPalette> {
+    pub fn new(bytes: &[u8]) -> Result<ColorPalette> {
         if bytes.len() != PALETTE_SIZE {
             bail!(
                 "Expected color palette to be exactly {} bytes",
@@ -25,7 +29,27 @@ impl ColorPalette {
             );
         }
 
-        Ok(ColorPalette { data: bytes.into() })
+        let six_bit = bytes[..COLORS_SIZE].iter().all(|v| *v < 64);
+
+        Ok(ColorPalette {
+            data: bytes.into(),
+            six_bit,
+        })
+    }
+
+    /// RGB value of palette entry `index`.
+    pub fn color(&self, index: u8) -> [u8; 3] {
+        let i = 3 * index as usize;
+        let mut rgb = [self.data[i], self.data[i + 1], self.data[i + 2]];
+        if self.six_bit {
+            rgb.iter_mut().for_each(|v| *v = (*v << 2) | (*v >> 4));
+        }
+        rgb
+    }
+
+    /// Palette entry closest to a 15 bit (5-5-5) color.
+    pub fn index_of(&self, color: u16) -> u8 {
+        self.data[COLORS_SIZE + (color & 0x7fff) as usize]
     }
 }
 
@@ -1,7 +1,9 @@
+use super::ColorPalette;
 use anyhow::{bail, Result};
 use bevy::{
     asset::{AssetLoader, LoadContext, LoadedAsset},
     reflect::TypeUuid,
+    render::texture::Image,
     utils::BoxedFuture,
 };
 
@@ -18,7 +20,7 @@ pub struct ColorRemapTable {
 }
 
 impl ColorRemapTable {
-    fn new(bytes: &[u8]) -> Result<ColorRemapTable> {
+    pub fn new(bytes: &[u8]) -> Result<ColorRemapTable> {
         if bytes.len() != TABLE_SIZE {
             bail!(
                 "Expected color remap table to be exactly {} bytes",
@@ -28,6 +30,37 @@ impl ColorRemapTable {
 
         Ok(ColorRemapTable { data: bytes.into() })
     }
+
+    /// Palette entry that replaces entry `index`.
+    pub fn remap(&self, index: u8) -> u8 {
+        self.data[index as usize]
+    }
+
+    /// Remap a 15 bit color from an animation:
+    /// `palette[3 * rmp[palette[768 + color]]]`.
+    pub fn remap_color(&self, palette: &ColorPalette, color: u16) -> [u8; 3] {
+        palette.color(self.remap(palette.index_of(color)))
+    }
+
+    /// Recolor an RGBA image that was decoded from 15 bit colors, such as the
+    /// sprite sheets of 16bpp animations. Transparent pixels are left alone.
+    pub fn remap_image(&self, palette: &ColorPalette, image: &Image) -> Image {
+        let mut image = image.clone();
+        for pixel in image.data.chunks_exact_mut(4) {
+            if pixel[3] == 0 {
+                continue;
+            }
+
+            let color = ((pixel[0] as u16 >> 3) << 10)
+                | ((pixel[1] as u16 >> 3) << 5)
+                | (pixel[2] as u16 >> 3);
+            let [r, g, b] = self.remap_color(palette, color);
+            pixel[0] = r;
+            pixel[1] = g;
+            pixel[2] = b;
+        }
+        image
+    }
 }
 
 impl AssetLoader for ColorRemapTableAssetLoader {
@@ -0,0 +1,119 @@
+use anyhow::{bail, Result};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+
+/// Loads the text files that list the data files of the game, like
+/// DATA/ANI/MASTER.ALI for animations and DATA/RES/SOUNDLST.RES for sounds.
+///
+/// Only the file names are of interest. Every word with an extension is taken
+/// as one, everything after a `;` is a comment.
+#[derive(Default)]
+pub struct FileListAssetLoader;
+
+#[derive(Debug, Default, TypeUuid)]
+#[uuid = "b0d6a3c2-6f0e-4d51-8f4e-3c9a1a7d2e18"]
+pub struct FileList {
+    /// File names in the order of the list, without duplicates.
+    pub files: Vec<String>,
+}
+
+impl FileList {
+    /// Files with the given extension, ignoring case.
+    pub fn with_extension<'a>(&'a self, ext: &'a str) -> impl Iterator<Item = &'a str> {
+        self.files.iter().map(|s| s.as_str()).filter(move |file| {
+            matches!(file.rsplit_once('.'), Some((_, e)) if e.eq_ignore_ascii_case(ext))
+        })
+    }
+
+    pub fn contains(&self, file: &str) -> bool {
+        self.files.iter().any(|f| f.eq_ignore_ascii_case(file))
+    }
+}
+
+impl AssetLoader for FileListAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(parse_file_list(bytes)?));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ali", "ALI", "res", "RES"]
+    }
+}
+
+pub fn parse_file_list(bytes: &[u8]) -> Result<FileList> {
+    if bytes.contains(&0) {
+        bail!("File list is not a text file");
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let mut list = FileList::default();
+
+    for line in text.lines() {
+        let line = line.split(';').next().unwrap();
+        let words = line
+            .split(|c: char| c.is_whitespace() || matches!(c, ',' | '=' | '"'))
+            .filter(|word| is_file_name(word));
+
+        for word in words {
+            if !list.contains(word) {
+                list.files.push(word.to_owned());
+            }
+        }
+    }
+
+    Ok(list)
+}
+
+/// DOS file names: up to 8 characters, a dot and up to 3 characters of
+/// extension. Extensions start with a letter, so numbers are not taken as file
+/// names.
+fn is_file_name(word: &str) -> bool {
+    let valid = |s: &str, max: usize| {
+        !s.is_empty()
+            && s.len() <= max
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '~'))
+    };
+
+    match word.split_once('.') {
+        Some((name, ext)) => {
+            valid(name, 8) && valid(ext, 3) && ext.starts_with(|c: char| c.is_ascii_alphabetic())
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_file_names_from_any_layout() {
+        let list = parse_file_list(
+            b"; master list\r\n\
+              ALIENS1.ANI\r\n\
+              -SOUND_BOMB, bmdrop1.rss, BMDROP2.RSS ; bmdrop3.rss\r\n\
+              aliens1.ani 1.5 version2.0\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            list.files,
+            vec!["ALIENS1.ANI", "bmdrop1.rss", "BMDROP2.RSS"]
+        );
+        assert_eq!(
+            list.with_extension("RSS").collect::<Vec<_>>(),
+            vec!["bmdrop1.rss", "BMDROP2.RSS"]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use anyhow::{bail, Result};
+use byteorder::{WriteBytesExt, LE};
+use std::collections::HashMap;
+
+/// Writes 8 bit PCX files with a 256 color palette, the format of the images
+/// of the original game. The counterpart of `PcxDecoder`.
+///
+/// RGBA images with more than 256 colors are quantized, the alpha channel is
+/// dropped.
+#[derive(Debug, Clone, Default)]
+pub struct PcxEncoder {
+    /// Top left corner of the image window, see `PcxInfo::origin`.
+    pub origin: (u16, u16),
+}
+
+impl PcxEncoder {
+    pub fn encode_rgba(&self, width: u32, height: u32, pixels: &[u8]) -> Result<Vec<u8>> {
+        if pixels.len() != 4 * width as usize * height as usize {
+            bail!(
+                "{} bytes of pixels don't match a {}x{} RGBA image",
+                pixels.len(),
+                width,
+                height
+            );
+        }
+
+        let colors: Vec<[u8; 3]> = pixels.chunks_exact(4).map(|p| [p[0], p[1], p[2]]).collect();
+
+        let mut counts: HashMap<[u8; 3], usize> = HashMap::new();
+        for color in colors.iter() {
+            *counts.entry(*color).or_default() += 1;
+        }
+
+        let palette = if counts.len() <= 256 {
+            let mut palette: Vec<[u8; 3]> = counts.keys().copied().collect();
+            palette.sort_unstable();
+            palette
+        } else {
+            median_cut(counts.into_iter().collect(), 256)
+        };
+
+        let mut lookup: HashMap<[u8; 3], u8> = HashMap::new();
+        let indices: Vec<u8> = colors
+            .iter()
+            .map(|color| {
+                *lookup
+                    .entry(*color)
+                    .or_insert_with(|| nearest(&palette, *color))
+            })
+            .collect();
+
+        self.encode_indexed(width, height, &indices, &palette)
+    }
+
+    /// Encodes palette indices. The palette may hold up to 256 colors, the
+    /// remaining entries are written as black.
+    pub fn encode_indexed(
+        &self,
+        width: u32,
+        height: u32,
+        indices: &[u8],
+        palette: &[[u8; 3]],
+    ) -> Result<Vec<u8>> {
+        if indices.len() != width as usize * height as usize {
+            bail!(
+                "{} indices don't match a {}x{} image",
+                indices.len(),
+                width,
+                height
+            );
+        }
+        if palette.len() > 256 {
+            bail!("Palette has {} colors, at most 256 fit", palette.len());
+        }
+        if width == 0 || height == 0 {
+            bail!("PCX images can't be empty");
+        }
+
+        let (min_x, min_y) = self.origin;
+        let max_x = (min_x as u64 + width as u64 - 1).try_into();
+        let max_y = (min_y as u64 + height as u64 - 1).try_into();
+        let (max_x, max_y): (u16, u16) = match (max_x, max_y) {
+            (Ok(max_x), Ok(max_y)) => (max_x, max_y),
+            _ => bail!(
+                "{}x{} at {:?} exceeds the PCX image window",
+                width,
+                height,
+                self.origin
+            ),
+        };
+
+        // Scanlines are padded to an even number of bytes.
+        let line_bytes = (width as usize + 1) & !1;
+        let line_bytes_field = match u16::try_from(line_bytes) {
+            Ok(line_bytes) => line_bytes,
+            Err(_) => bail!("{} pixel wide scanlines don't fit in a PCX header", width),
+        };
+
+        let mut data = vec![0x0A, 5, 1, 8];
+        for v in [min_x, min_y, max_x, max_y, 72, 72] {
+            data.write_u16::<LE>(v)?;
+        }
+        // Unused EGA palette and reserved byte.
+        data.extend([0; 49]);
+        data.write_u8(1)?;
+        data.write_u16::<LE>(line_bytes_field)?;
+        // Color palette.
+        data.write_u16::<LE>(1)?;
+        data.resize(128, 0);
+
+        for line in indices.chunks_exact(width as usize) {
+            let mut line = line.to_vec();
+            line.resize(line_bytes, 0);
+            encode_rle(&line, &mut data);
+        }
+
+        data.push(0x0C);
+        for i in 0..256 {
+            data.extend(palette.get(i).copied().unwrap_or_default());
+        }
+
+        Ok(data)
+    }
+}
+
+/// PCX run length encoding of a scanline. Runs hold up to 63 bytes, single
+/// bytes that look like a run marker are written as a run of one.
+fn encode_rle(line: &[u8], data: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < line.len() {
+        let value = line[i];
+        let run = line[i..]
+            .iter()
+            .take(63)
+            .take_while(|v| **v == value)
+            .count();
+
+        if run > 1 || value >= 0xC0 {
+            data.push(0xC0 | run as u8);
+        }
+        data.push(value);
+        i += run;
+    }
+}
+
+/// Reduces `colors`, with the number of pixels of each color, to a palette of
+/// at most `size` colors. The box of colors spanning the widest range of a
+/// channel is split at its median, until there are `size` boxes.
+fn median_cut(colors: Vec<([u8; 3], usize)>, size: usize) -> Vec<[u8; 3]> {
+    let mut boxes = vec![colors];
+
+    while boxes.len() < size {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(i, colors)| {
+                let (channel, range) = widest_channel(colors);
+                (i, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range);
+
+        let (i, channel) = match widest {
+            Some((i, channel, _)) => (i, channel),
+            None => break,
+        };
+
+        let mut colors = boxes.swap_remove(i);
+        colors.sort_unstable_by_key(|(color, _)| color[channel]);
+
+        // Split where half of the pixels are on either side.
+        let total: usize = colors.iter().map(|(_, count)| count).sum();
+        let mut seen = 0;
+        let split = colors
+            .iter()
+            .position(|(_, count)| {
+                seen += count;
+                seen * 2 >= total
+            })
+            .unwrap_or(0)
+            .clamp(0, colors.len() - 2)
+            + 1;
+
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|colors| average(colors)).collect()
+}
+
+fn widest_channel(colors: &[([u8; 3], usize)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let values = colors.iter().map(|(color, _)| color[channel]);
+            let range = values.clone().max().unwrap() - values.min().unwrap();
+            (channel, range)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap()
+}
+
+/// Average color of a box, weighted by the number of pixels.
+fn average(colors: &[([u8; 3], usize)]) -> [u8; 3] {
+    let total: usize = colors.iter().map(|(_, count)| count).sum();
+    let mut sum = [0usize; 3];
+    for (color, count) in colors {
+        for channel in 0..3 {
+            sum[channel] += color[channel] as usize * count;
+        }
+    }
+    sum.map(|s| ((s + total / 2) / total) as u8)
+}
+
+fn nearest(palette: &[[u8; 3]], color: [u8; 3]) -> u8 {
+    let distance = |entry: &[u8; 3]| -> i32 {
+        (0..3)
+            .map(|channel| (entry[channel] as i32 - color[channel] as i32).pow(2))
+            .sum()
+    };
+
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance(entry))
+        .map(|(i, _)| i as u8)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset_loaders::pcx_image::PcxDecoder;
+
+    fn decode(data: &[u8]) -> Vec<u8> {
+        PcxDecoder::new(data)
+            .unwrap()
+            .read_image_data()
+            .unwrap()
+            .data
+    }
+
+    #[test]
+    fn round_trip_few_colors() {
+        // Odd width, runs longer than 63 and values that look like run
+        // markers.
+        let (width, height) = (101, 3);
+        let pixels: Vec<u8> = (0..width * height)
+            .flat_map(|i| match i % width {
+                0..=69 => [200, 201, 202, 255],
+                x => [(x % 7) as u8 * 30, 0xC5, 0xFF, 255],
+            })
+            .collect();
+
+        let data = PcxEncoder::default()
+            .encode_rgba(width, height, &pixels)
+            .unwrap();
+        assert_eq!(decode(&data), pixels);
+    }
+
+    #[test]
+    fn quantizes_many_colors() {
+        let (width, height) = (64, 64);
+        let pixels: Vec<u8> = (0..width * height)
+            .flat_map(|i| [(i % 64 * 4) as u8, (i / 64 * 4) as u8, 128, 255])
+            .collect();
+
+        let data = PcxEncoder::default()
+            .encode_rgba(width, height, &pixels)
+            .unwrap();
+        let decoded = decode(&data);
+
+        assert_eq!(decoded.len(), pixels.len());
+        for (a, b) in decoded.chunks(4).zip(pixels.chunks(4)) {
+            for channel in 0..3 {
+                assert!((a[channel] as i32 - b[channel] as i32).abs() <= 16);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_origin() {
+        let encoder = PcxEncoder { origin: (3, 4) };
+        let data = encoder
+            .encode_indexed(1, 1, &[1], &[[0; 3], [9; 3]])
+            .unwrap();
+
+        let mut decoder = PcxDecoder::new(&data).unwrap();
+        assert_eq!(decoder.read_image_data().unwrap().data, vec![9, 9, 9, 255]);
+        assert_eq!(decoder.origin(), bevy::math::UVec2::new(3, 4));
+    }
+
+    #[test]
+    fn rejects_scanlines_wider_than_the_header() {
+        let encoder = PcxEncoder::default();
+        for width in [65535, 65536] {
+            let indices = vec![0; width as usize];
+            assert!(encoder
+                .encode_indexed(width, 1, &indices, &[[0; 3]])
+                .is_err());
+        }
+
+        let indices = vec![0; 65534];
+        let data = encoder
+            .encode_indexed(65534, 1, &indices, &[[0; 3]])
+            .unwrap();
+        assert_eq!(u16::from_le_bytes([data[66], data[67]]), 65534);
+    }
+}
@@ -1,14 +1,23 @@
+use super::sidecar::read_sidecar;
 use anyhow::{bail, Result};
 use bevy::{
-    asset::{AssetLoader, LoadContext, LoadedAsset},
+    asset::{AssetLoader, AssetPath, Handle, LoadContext, LoadedAsset},
+    math::UVec2,
+    reflect::TypeUuid,
     render::render_resource::{Extent3d, TextureDimension, TextureFormat},
     render::texture::Image,
     utils::BoxedFuture,
 };
 use byteorder::{ReadBytesExt, LE};
+use serde::Deserialize;
 use std::fmt::Debug;
 use std::io::{Cursor, Read, Seek, SeekFrom};
 
+/// Name of the sidecar file that sets the `PcxSettings` of all PCX files in
+/// its directory. A single file is configured by a sidecar named after it
+/// with a `.json` suffix, e.g. `MAINMENU.PCX.json`, which takes precedence.
+const PCX_SIDECAR: &str = "pcx.json";
+
 /// Inspired by:
 /// https://github.com/image-rs/image/blob/master/src/codecs/bmp/decoder.rs
 ///
@@ -18,6 +27,87 @@ use std::io::{Cursor, Read, Seek, SeekFrom};
 #[derive(Default)]
 pub struct PcxImageAssetLoader;
 
+/// Settings for loading a PCX file, read from a sidecar file. Only images
+/// with a palette use them:
+///
+/// ```json
+/// { "transparent_index": 0 }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PcxSettings {
+    /// See `IndexedImage::transparent_index`.
+    pub transparent_index: Option<u8>,
+}
+
+/// Header information of a PCX image that doesn't fit into an `Image`. It is
+/// loaded as the `info` label of the file, e.g. `"data/RES/FIELD0.PCX#info"`.
+#[derive(Debug, TypeUuid)]
+#[uuid = "06512385-3d20-4aef-b434-62b236813b95"]
+pub struct PcxInfo {
+    pub image: Handle<Image>,
+    /// Top left corner of the image window. Images that were cut out of a
+    /// bigger picture keep their position in it.
+    pub origin: UVec2,
+}
+
+/// Palette indices of a PCX image with up to 256 colors, loaded as the
+/// `indexed` label of the file, e.g. `"data/RES/MAINMENU.PCX#indexed"`.
+/// Unlike the default `Image` it can be rendered with a transparent color and
+/// with changed palette entries, e.g. to highlight parts of a menu.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "5a6f7e63-0d5c-4c4e-9a3b-2f1f8d6b7c21"]
+pub struct IndexedImage {
+    pub width: u32,
+    pub height: u32,
+    /// One palette index per pixel, row by row.
+    pub indices: Vec<u8>,
+    pub palette: Vec<[u8; 3]>,
+    /// Palette index rendered fully transparent, set by the `PcxSettings`
+    /// sidecar when loading.
+    pub transparent_index: Option<u8>,
+}
+
+impl IndexedImage {
+    pub fn render(&self) -> Image {
+        self.render_with_palette(&self.palette)
+    }
+
+    /// Renders the image with another palette. Indices past the end of the
+    /// palette are black.
+    pub fn render_with_palette(&self, palette: &[[u8; 3]]) -> Image {
+        let data = self
+            .indices
+            .iter()
+            .flat_map(|index| {
+                let [r, g, b] = palette.get(*index as usize).copied().unwrap_or_default();
+                let alpha = if Some(*index) == self.transparent_index {
+                    0
+                } else {
+                    255
+                };
+                [r, g, b, alpha]
+            })
+            .collect();
+
+        rgba_image(self.width, self.height, data)
+    }
+
+    /// Renders the image with the palette entries in `swaps` replaced, given
+    /// as pairs of index and new color.
+    pub fn render_with_swaps(&self, swaps: &[(u8, [u8; 3])]) -> Image {
+        let mut palette = self.palette.clone();
+        for (index, color) in swaps {
+            let index = *index as usize;
+            if index >= palette.len() {
+                palette.resize(index + 1, [0; 3]);
+            }
+            palette[index] = *color;
+        }
+        self.render_with_palette(&palette)
+    }
+}
+
 impl AssetLoader for PcxImageAssetLoader {
     fn load<'a>(
         &'a self,
@@ -26,8 +116,23 @@ impl AssetLoader for PcxImageAssetLoader {
     ) -> BoxedFuture<'a, Result<()>> {
         Box::pin(async move {
             let mut decoder = PcxDecoder::new(bytes)?;
-            let texture = decoder.read_image_data()?;
-            load_context.set_default_asset(LoadedAsset::new(texture));
+            if decoder.is_indexed() {
+                let settings: PcxSettings = read_sidecar(load_context, PCX_SIDECAR).await?;
+                let mut indexed = decoder.read_indexed_image()?;
+                indexed.transparent_index = settings.transparent_index;
+                load_context.set_default_asset(LoadedAsset::new(indexed.render()));
+                load_context.set_labeled_asset("indexed", LoadedAsset::new(indexed));
+            } else {
+                let texture = decoder.read_image_data()?;
+                load_context.set_default_asset(LoadedAsset::new(texture));
+            }
+
+            let image = load_context.get_handle(AssetPath::new_ref(load_context.path(), None));
+            let info = PcxInfo {
+                image,
+                origin: decoder.origin(),
+            };
+            load_context.set_labeled_asset("info", LoadedAsset::new(info));
             Ok(())
         })
     }
@@ -61,8 +166,50 @@ enum PcxColorPlanes {
     RGBA,
 }
 
+impl PcxBitsPerPixelPlane {
+    fn bits(&self) -> usize {
+        match self {
+            PcxBitsPerPixelPlane::Bits1 => 1,
+            PcxBitsPerPixelPlane::Bits2 => 2,
+            PcxBitsPerPixelPlane::Bits4 => 4,
+            PcxBitsPerPixelPlane::Bits8 => 8,
+        }
+    }
+}
+
+impl PcxColorPlanes {
+    fn count(&self) -> usize {
+        match self {
+            PcxColorPlanes::Single => 1,
+            PcxColorPlanes::RGB => 3,
+            PcxColorPlanes::RGBA => 4,
+        }
+    }
+}
+
+/// Used by version 3 files, which don't store a palette in the header.
+const DEFAULT_EGA_PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0xaa),
+    (0x00, 0xaa, 0x00),
+    (0x00, 0xaa, 0xaa),
+    (0xaa, 0x00, 0x00),
+    (0xaa, 0x00, 0xaa),
+    (0xaa, 0x55, 0x00),
+    (0xaa, 0xaa, 0xaa),
+    (0x55, 0x55, 0x55),
+    (0x55, 0x55, 0xff),
+    (0x55, 0xff, 0x55),
+    (0x55, 0xff, 0xff),
+    (0xff, 0x55, 0x55),
+    (0xff, 0x55, 0xff),
+    (0xff, 0xff, 0x55),
+    (0xff, 0xff, 0xff),
+];
+
+/// Decodes the PCX images of `PcxImageAssetLoader`.
 #[derive(Default, Debug)]
-struct PcxDecoder<'a> {
+pub struct PcxDecoder<'a> {
     cursor: Cursor<&'a [u8]>,
     has_loaded_metadata: bool,
     version: Option<PcxVersion>,
@@ -76,6 +223,7 @@ struct PcxDecoder<'a> {
     height: u16,
     horz_dpi: u16,
     vert_dpi: u16,
+    ega_palette: Vec<(u8, u8, u8)>,
     palette: Option<Vec<(u8, u8, u8)>>,
     color_planes: Option<PcxColorPlanes>,
     color_plane_bytes: u16,
@@ -107,31 +255,40 @@ impl<'a> Iterator for RleIterator<'a> {
     fn next(&mut self) -> Option<u8> {
         if self.rle_len > 0 {
             self.rle_len -= 1;
-            return Some(self.rle_data.unwrap());
+            return self.rle_data;
         }
 
-        let control = match self.cursor.read_u8() {
-            Ok(b) => b,
-            Err(_) => return None,
-        };
-
-        // RLE sequence if highest two bits are set.
-        if control & 0b11000000 == 0b11000000 {
-            self.rle_len = (control & 0b00111111) - 1;
-            let data = match self.cursor.read_u8() {
+        loop {
+            let control = match self.cursor.read_u8() {
                 Ok(b) => b,
                 Err(_) => return None,
             };
-            self.rle_data = Some(data);
-            Some(data)
-        } else {
-            Some(control)
+
+            // RLE sequence if highest two bits are set.
+            if control & 0b11000000 == 0b11000000 {
+                let data = match self.cursor.read_u8() {
+                    Ok(b) => b,
+                    Err(_) => return None,
+                };
+
+                // A run of zero bytes holds no data, continue after it.
+                let len = control & 0b00111111;
+                if len == 0 {
+                    continue;
+                }
+
+                self.rle_len = len - 1;
+                self.rle_data = Some(data);
+                return Some(data);
+            } else {
+                return Some(control);
+            }
         }
     }
 }
 
 impl<'a> PcxDecoder<'a> {
-    fn new(bytes: &'a [u8]) -> Result<Self> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
         let mut decoder = PcxDecoder {
             cursor: Cursor::new(bytes),
             ..Default::default()
@@ -183,17 +340,24 @@ impl<'a> PcxDecoder<'a> {
         self.min_y = self.cursor.read_u16::<LE>()?;
         self.max_x = self.cursor.read_u16::<LE>()?;
         self.max_y = self.cursor.read_u16::<LE>()?;
+        if self.max_x < self.min_x || self.max_y < self.min_y {
+            bail!(
+                "Invalid image window ({}, {}) - ({}, {})",
+                self.min_x,
+                self.min_y,
+                self.max_x,
+                self.max_y
+            );
+        }
         self.width = self.max_x - self.min_x + 1;
         self.height = self.max_y - self.min_y + 1;
         self.horz_dpi = self.cursor.read_u16::<LE>()?;
         self.vert_dpi = self.cursor.read_u16::<LE>()?;
 
-        // 48 bytes of EGA pallette (only used if bits_per_pixel_plane = 4).
+        // 48 bytes of EGA pallette, used by images with up to 16 colors.
         let mut ega_palette = [0; 48];
         self.cursor.read_exact(&mut ega_palette)?;
-        if self.bits_per_pixel_plane.unwrap() == PcxBitsPerPixelPlane::Bits4 {
-            self.palette = Some(ega_palette.chunks(3).map(|c| (c[0], c[1], c[2])).collect());
-        }
+        self.ega_palette = ega_palette.chunks(3).map(|c| (c[0], c[1], c[2])).collect();
 
         // First reserved field.
         self.cursor.read_u8()?;
@@ -207,29 +371,42 @@ impl<'a> PcxDecoder<'a> {
 
         self.color_plane_bytes = self.cursor.read_u16::<LE>()?;
 
-        self.grayscale_palette = match self.cursor.read_u8()? {
-            0 => false,
-            1 => true,
+        // Palette type, 1 is color or black and white, 2 grayscale. Older
+        // files leave it at 0.
+        self.grayscale_palette = match self.cursor.read_u16::<LE>()? {
+            0 | 1 => false,
+            2 => true,
             other => bail!("Unknown value for palette type: {}", other),
         };
 
         self.source_horz_resolution = self.cursor.read_u16::<LE>()?;
         self.source_vert_resolution = self.cursor.read_u16::<LE>()?;
 
-        // 54 bytes of reserved field(s).
-        self.cursor.seek(SeekFrom::Current(54))?;
+        // 54 bytes of reserved field(s), the image data starts at 128.
+        self.cursor.seek(SeekFrom::Start(128))?;
 
         Ok(())
     }
 
+    /// Images with up to 16 colors use the palette in the header, 256 color
+    /// images the one at the end of the file.
     fn read_palette(&mut self) -> Result<()> {
         if self.palette.is_some() {
             return Ok(());
         }
 
-        let bpp = self.bits_per_pixel_plane.unwrap();
-        if bpp != PcxBitsPerPixelPlane::Bits8 {
-            bail!("Don't know how to read palette for {:?}", bpp);
+        let bits = self.bits_per_pixel_plane.unwrap().bits();
+        let planes = self.color_planes.unwrap().count();
+        if bits == 1 && planes == 1 {
+            self.palette = Some(vec![(0, 0, 0), (255, 255, 255)]);
+            return Ok(());
+        }
+        if bits < 8 {
+            self.palette = Some(match self.version.unwrap() {
+                PcxVersion::V3 => DEFAULT_EGA_PALETTE.to_vec(),
+                _ => self.ega_palette.clone(),
+            });
+            return Ok(());
         }
 
         // 256 color palette is in the last 768 bytes of the file.
@@ -254,50 +431,298 @@ impl<'a> PcxDecoder<'a> {
         Ok(())
     }
 
-    fn read_image_data(&mut self) -> Result<Image> {
-        if self.bits_per_pixel_plane.unwrap() != PcxBitsPerPixelPlane::Bits8 {
-            bail!("Only 8 bits per pixel plane are supported");
-        }
+    pub fn origin(&self) -> UVec2 {
+        UVec2::new(self.min_x as u32, self.min_y as u32)
+    }
 
-        if self.color_planes.unwrap() != PcxColorPlanes::Single {
-            bail!("Only a single color plane is supported");
+    /// Bytes per scanline of each color plane. Writers usually pad it to an
+    /// even count, the bytes past the last pixel are ignored.
+    fn line_bytes(&self) -> Result<usize> {
+        let bits = self.bits_per_pixel_plane.unwrap().bits();
+        let required = (self.width as usize * bits + 7) / 8;
+
+        match self.color_plane_bytes as usize {
+            // Some writers leave the field empty, assume unpadded scanlines.
+            0 => Ok(required),
+            line_bytes if line_bytes < required => bail!(
+                "{} bytes per line are too few for {} pixels of {} bits",
+                line_bytes,
+                self.width,
+                bits
+            ),
+            line_bytes => Ok(line_bytes),
         }
+    }
+
+    /// Reads the encoded scanlines. Every scanline holds `line_bytes` for each
+    /// color plane, one plane after the other.
+    fn read_scanlines(&mut self, line_bytes: usize) -> Result<Vec<u8>> {
+        let planes = self.color_planes.unwrap().count();
+        let data_size = self.height as usize * planes * line_bytes;
 
-        let data_size = self.width as usize * self.height as usize;
-        let pixel_indices: Vec<u8> = if self.rle {
+        let data: Vec<u8> = if self.rle {
             RleIterator::new(self.cursor.clone())
                 .take(data_size)
                 .collect()
         } else {
             let mut data = vec![0; data_size];
-            for value in data.iter_mut() {
-                *value = self.cursor.read_u8()?;
-            }
+            self.cursor.read_exact(&mut data)?;
             data
         };
 
-        self.read_palette()?;
+        if data.len() != data_size {
+            bail!(
+                "Image data ended after {} of {} bytes",
+                data.len(),
+                data_size
+            );
+        }
 
-        let palette = self.palette.as_ref().unwrap();
-        let image_data: Vec<u8> = pixel_indices
-            .iter()
-            .flat_map(|i| {
-                let (r, g, b) = palette.get(*i as usize).unwrap();
-                vec![*r, *g, *b, 255]
+        Ok(data)
+    }
+
+    /// Whether the pixels are palette indices, see `read_indexed_image`.
+    pub fn is_indexed(&self) -> bool {
+        let bits = self.bits_per_pixel_plane.unwrap().bits();
+        let planes = self.color_planes.unwrap().count();
+        matches!((planes, bits), (1, _) | (3 | 4, 1))
+    }
+
+    pub fn read_image_data(&mut self) -> Result<Image> {
+        if self.is_indexed() {
+            return Ok(self.read_indexed_image()?.render());
+        }
+
+        let bits = self.bits_per_pixel_plane.unwrap().bits();
+        let planes = self.color_planes.unwrap().count();
+        let width = self.width as usize;
+        let line_bytes = self.line_bytes()?;
+
+        let image_data: Vec<u8> = match (planes, bits) {
+            // True color, the planes hold the red, green, blue and alpha
+            // channel of the scanline.
+            (3 | 4, 8) => self
+                .read_scanlines(line_bytes)?
+                .chunks_exact(planes * line_bytes)
+                .flat_map(|line| {
+                    (0..width).flat_map(move |x| {
+                        let channel = |plane: usize| line[plane * line_bytes + x];
+                        let alpha = if planes == 4 { channel(3) } else { 255 };
+                        [channel(0), channel(1), channel(2), alpha]
+                    })
+                })
+                .collect(),
+            (planes, bits) => bail!(
+                "Unsupported combination of {} color planes with {} bits",
+                planes,
+                bits
+            ),
+        };
+
+        Ok(rgba_image(
+            self.width as u32,
+            self.height as u32,
+            image_data,
+        ))
+    }
+
+    /// Reads palette indices, either packed into a single plane or spread as
+    /// one bit over every plane (EGA).
+    pub fn read_indexed_image(&mut self) -> Result<IndexedImage> {
+        if !self.is_indexed() {
+            bail!("Image has no palette");
+        }
+
+        let bits = self.bits_per_pixel_plane.unwrap().bits();
+        let planes = self.color_planes.unwrap().count();
+        let width = self.width as usize;
+        let line_bytes = self.line_bytes()?;
+
+        let data = self.read_scanlines(line_bytes)?;
+        let indices = data
+            .chunks_exact(planes * line_bytes)
+            .flat_map(|line| {
+                (0..width).map(move |x| {
+                    (0..planes).fold(0, |index, plane| {
+                        let value = packed_value(&line[plane * line_bytes..], x, bits);
+                        index | (value << plane)
+                    })
+                })
             })
             .collect();
 
-        let texture = Image::new(
-            Extent3d {
-                width: self.width as u32,
-                height: self.height as u32,
-                depth_or_array_layers: 1,
-            },
-            TextureDimension::D2,
-            image_data,
-            TextureFormat::Rgba8UnormSrgb,
+        self.read_palette()?;
+        let palette = self
+            .palette
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|&(r, g, b)| [r, g, b])
+            .collect();
+
+        Ok(IndexedImage {
+            width: self.width as u32,
+            height: self.height as u32,
+            indices,
+            palette,
+            transparent_index: None,
+        })
+    }
+}
+
+fn rgba_image(width: u32, height: u32, data: Vec<u8>) -> Image {
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// Value of pixel `x` in a scanline with `bits` per pixel, the leftmost pixel
+/// is stored in the highest bits of a byte.
+fn packed_value(line: &[u8], x: usize, bits: usize) -> u8 {
+    let byte = line[x * bits / 8];
+    let shift = 8 - bits - (x * bits % 8);
+    (byte >> shift) & (0xff >> (8 - bits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Header of a synthetic version 5 PCX file.
+    fn header(bits: u8, planes: u8, window: [u16; 4], line_bytes: u16) -> Vec<u8> {
+        let mut data = vec![0x0A, 5, 1, bits];
+        for v in window.iter().chain(&[72, 72]) {
+            data.extend(v.to_le_bytes());
+        }
+        let ega_palette: Vec<u8> = (0..16).flat_map(|i| [i * 16, i, 0]).collect();
+        data.extend(ega_palette);
+        data.extend([0, planes]);
+        data.extend(line_bytes.to_le_bytes());
+        data.extend(1u16.to_le_bytes());
+        data.resize(128, 0);
+        data
+    }
+
+    /// RLE encodes every byte on its own, so values above 0xC0 are allowed.
+    fn encode(bytes: &[u8]) -> Vec<u8> {
+        bytes.iter().flat_map(|b| [0xC1, *b]).collect()
+    }
+
+    fn vga_palette(data: &mut Vec<u8>) {
+        data.push(0x0C);
+        data.extend((0..=255).flat_map(|i| [i, 255 - i, 0]));
+    }
+
+    fn red(image: &Image) -> Vec<u8> {
+        image.data.chunks(4).map(|p| p[0]).collect()
+    }
+
+    #[test]
+    fn odd_width_8_bit_skips_padding() {
+        // 3 pixels wide, scanlines are padded to 4 bytes.
+        let mut data = header(8, 1, [0, 0, 2, 1], 4);
+        data.extend(encode(&[1, 2, 3, 0xEE, 4, 5, 6, 0xEE]));
+        vga_palette(&mut data);
+
+        let image = PcxDecoder::new(&data).unwrap().read_image_data().unwrap();
+        assert_eq!(image.texture_descriptor.size.width, 3);
+        assert_eq!(image.texture_descriptor.size.height, 2);
+        assert_eq!(red(&image), vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn odd_width_4_bit_skips_padding() {
+        // 3 pixels of 4 bits need 2 bytes, padded to 4.
+        let mut data = header(4, 1, [0, 0, 2, 1], 4);
+        data.extend(encode(&[0x12, 0x3F, 0xFF, 0xFF, 0x45, 0x6F, 0xFF, 0xFF]));
+
+        let image = PcxDecoder::new(&data).unwrap().read_image_data().unwrap();
+        assert_eq!(red(&image), vec![16, 32, 48, 64, 80, 96]);
+    }
+
+    #[test]
+    fn odd_width_planar_skips_padding() {
+        // 1 pixel wide RGB, each plane padded to 2 bytes.
+        let mut data = header(8, 3, [0, 0, 0, 1], 2);
+        data.extend(encode(&[
+            10, 0xEE, 20, 0xEE, 30, 0xEE, 40, 0xEE, 50, 0xEE, 60, 0xEE,
+        ]));
+
+        let image = PcxDecoder::new(&data).unwrap().read_image_data().unwrap();
+        assert_eq!(image.data, vec![10, 20, 30, 255, 40, 50, 60, 255]);
+    }
+
+    #[test]
+    fn runs_may_cross_scanlines() {
+        let mut data = header(8, 1, [0, 0, 2, 1], 4);
+        data.extend([0xC5, 9, 0xC2, 7, 0]);
+        vga_palette(&mut data);
+
+        let image = PcxDecoder::new(&data).unwrap().read_image_data().unwrap();
+        assert_eq!(red(&image), vec![9, 9, 9, 9, 7, 7]);
+    }
+
+    #[test]
+    fn keeps_window_origin() {
+        let mut data = header(8, 1, [5, 7, 7, 7], 4);
+        data.extend(encode(&[1, 2, 3, 0]));
+        vga_palette(&mut data);
+
+        let mut decoder = PcxDecoder::new(&data).unwrap();
+        let image = decoder.read_image_data().unwrap();
+        assert_eq!(decoder.origin(), UVec2::new(5, 7));
+        assert_eq!(image.texture_descriptor.size.width, 3);
+        assert_eq!(image.texture_descriptor.size.height, 1);
+    }
+
+    #[test]
+    fn rejects_too_short_scanlines() {
+        let mut data = header(8, 1, [0, 0, 2, 0], 2);
+        data.extend(encode(&[1, 2, 3, 4]));
+        vga_palette(&mut data);
+
+        assert!(PcxDecoder::new(&data).unwrap().read_image_data().is_err());
+    }
+
+    #[test]
+    fn indexed_image_keeps_indices() {
+        let mut data = header(8, 1, [0, 0, 2, 0], 4);
+        data.extend(encode(&[1, 2, 3, 0]));
+        vga_palette(&mut data);
+
+        let mut indexed = PcxDecoder::new(&data)
+            .unwrap()
+            .read_indexed_image()
+            .unwrap();
+        assert_eq!(indexed.indices, vec![1, 2, 3]);
+        assert_eq!(indexed.palette[2], [2, 253, 0]);
+
+        indexed.transparent_index = Some(2);
+        let swapped = indexed.render_with_swaps(&[(3, [7, 8, 9])]);
+        assert_eq!(
+            swapped.data,
+            vec![1, 254, 0, 255, 2, 253, 0, 0, 7, 8, 9, 255]
         );
+    }
 
-        Ok(texture)
+    #[test]
+    fn parses_sidecar_settings() {
+        let parse = |json: &[u8]| serde_json::from_slice::<PcxSettings>(json);
+        assert_eq!(
+            parse(br#"{ "transparent_index": 254 }"#).unwrap(),
+            PcxSettings {
+                transparent_index: Some(254)
+            }
+        );
+        assert_eq!(parse(b"{}").unwrap(), PcxSettings::default());
+        assert!(parse(br#"{ "transparent_index": 256 }"#).is_err());
+        assert!(parse(br#"{ "transparent": 0 }"#).is_err());
     }
 }
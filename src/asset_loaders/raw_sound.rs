@@ -1,25 +1,190 @@
-use anyhow::Result;
+use super::sidecar::read_sidecar;
+use anyhow::{bail, Context, Result};
 use bevy::{
     asset::{AssetLoader, LoadContext, LoadedAsset},
-    prelude::*,
+    audio::Decodable,
+    reflect::TypeUuid,
     utils::BoxedFuture,
 };
 use byteorder::{WriteBytesExt, LE};
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
 
-const SAMPLE_RATE: u32 = 22000;
-const NUM_CHANNELS: u16 = 2;
-const BITS_PER_SAMPLE: u16 = 16;
+/// Name of the sidecar file that sets the format of all RSS files in its
+/// directory. A single file is configured by a sidecar named after it with a
+/// `.json` suffix, e.g. `MENU.RSS.json`, which takes precedence.
+pub const RSS_SIDECAR: &str = "rss.json";
 
 /// RSS (supposedly) is an acronym for "Raw Sound Stream". It has no header, it
-/// only contains audio samples. The samples are stereo PCM, 16bit, 22000Hz,
-/// little endian.
+/// only contains audio samples. The samples of the original game are stereo
+/// PCM, 16bit, 22000Hz, little endian. Other formats can be configured with a
+/// sidecar file, see `RawSoundFormat`.
 ///
-/// Bevy is using the `rodio` library for audio decoding. An audio asset
-/// contains just the raw bytes of the loaded file. By prepending a WAV header
-/// to the data with the fixed parameters, rodio can play the asset.
+/// The samples are not converted when loading, `RawSound` decodes them while
+/// they are played. Long tracks like MENU.RSS are not copied into a WAV
+/// buffer.
 #[derive(Default)]
 pub struct RawSoundAssetLoader;
 
+/// Sample format of an RSS file. Sidecar files are JSON objects with any of
+/// the fields, the missing ones keep the format of the original game:
+///
+/// ```json
+/// { "sample_rate": 11025, "channels": 1 }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RawSoundFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// 8 or 16, 16 bit samples are little endian.
+    pub bits_per_sample: u16,
+    pub signed: bool,
+}
+
+impl Default for RawSoundFormat {
+    fn default() -> Self {
+        RawSoundFormat {
+            sample_rate: 22000,
+            channels: 2,
+            bits_per_sample: 16,
+            signed: true,
+        }
+    }
+}
+
+impl RawSoundFormat {
+    fn validate(&self) -> Result<()> {
+        if self.sample_rate == 0 {
+            bail!("Sample rate must not be 0");
+        }
+        if self.channels == 0 {
+            bail!("Number of channels must not be 0");
+        }
+        if !matches!(self.bits_per_sample, 8 | 16) {
+            bail!(
+                "Unsupported number of bits per sample: {}",
+                self.bits_per_sample
+            );
+        }
+        Ok(())
+    }
+
+    fn bytes_per_sample(&self) -> usize {
+        self.bits_per_sample as usize / 8
+    }
+
+    /// Size of one sample for every channel.
+    pub fn block_align(&self) -> usize {
+        self.channels as usize * self.bytes_per_sample()
+    }
+
+    /// Sample `index` of a channel interleaved stream, as signed 16 bit.
+    fn sample(&self, bytes: &[u8], index: usize) -> i16 {
+        match (self.bits_per_sample, self.signed) {
+            (8, true) => (bytes[index] as i8 as i16) << 8,
+            (8, false) => ((bytes[index] as i16) - 128) << 8,
+            (_, signed) => {
+                let value = u16::from_le_bytes([bytes[2 * index], bytes[2 * index + 1]]);
+                if signed {
+                    value as i16
+                } else {
+                    (value ^ 0x8000) as i16
+                }
+            }
+        }
+    }
+}
+
+/// The samples of an RSS file, played with `Res<Audio<RawSound>>`.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "2b3f1f7e-52b4-4d8e-9a55-8c3c1e0e6a43"]
+pub struct RawSound {
+    pub bytes: Arc<[u8]>,
+    pub format: RawSoundFormat,
+}
+
+impl RawSound {
+    /// Number of samples per channel, a trailing incomplete sample is
+    /// ignored.
+    pub fn len(&self) -> usize {
+        self.bytes.len() / self.format.block_align()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.len() as f64 / self.format.sample_rate as f64)
+    }
+
+    /// All samples, channels interleaved, as signed 16 bit.
+    pub fn samples(&self) -> RawSoundDecoder {
+        RawSoundDecoder {
+            sound: self.clone(),
+            position: 0,
+        }
+    }
+}
+
+impl Decodable for RawSound {
+    type Decoder = RawSoundDecoder;
+    type DecoderItem = i16;
+
+    fn decoder(&self) -> Self::Decoder {
+        self.samples()
+    }
+}
+
+/// Converts the samples of a `RawSound` while they are played.
+pub struct RawSoundDecoder {
+    sound: RawSound,
+    /// Index of the next sample, counting the samples of every channel.
+    position: usize,
+}
+
+impl RawSoundDecoder {
+    fn remaining(&self) -> usize {
+        self.sound.len() * self.sound.format.channels as usize - self.position
+    }
+}
+
+impl Iterator for RawSoundDecoder {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.remaining() == 0 {
+            return None;
+        }
+        let sample = self.sound.format.sample(&self.sound.bytes, self.position);
+        self.position += 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining(), Some(self.remaining()))
+    }
+}
+
+impl rodio::Source for RawSoundDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.remaining())
+    }
+
+    fn channels(&self) -> u16 {
+        self.sound.format.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sound.format.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.sound.duration())
+    }
+}
+
 impl AssetLoader for RawSoundAssetLoader {
     fn load<'a>(
         &'a self,
@@ -27,8 +192,10 @@ impl AssetLoader for RawSoundAssetLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<()>> {
         Box::pin(async move {
-            load_context.set_default_asset(LoadedAsset::new(AudioSource {
-                bytes: prepend_wav_header(bytes).unwrap().into(),
+            let format = read_format(load_context).await?;
+            load_context.set_default_asset(LoadedAsset::new(RawSound {
+                bytes: bytes.into(),
+                format,
             }));
             Ok(())
         })
@@ -39,17 +206,42 @@ impl AssetLoader for RawSoundAssetLoader {
     }
 }
 
+/// Reads the format from the sidecar of the file or of its directory.
+async fn read_format(load_context: &LoadContext<'_>) -> Result<RawSoundFormat> {
+    let format: RawSoundFormat = read_sidecar(load_context, RSS_SIDECAR).await?;
+    format
+        .validate()
+        .with_context(|| format!("Format of {:?}", load_context.path()))?;
+    Ok(format)
+}
+
+pub fn parse_format(bytes: &[u8]) -> Result<RawSoundFormat> {
+    let format: RawSoundFormat = serde_json::from_slice(bytes)?;
+    format.validate()?;
+    Ok(format)
+}
+
+/// Turns the samples of an RSS file into a WAV file. WAV stores 8 bit samples
+/// unsigned and 16 bit samples signed, samples with a different signedness
+/// are converted.
 // http://soundfile.sapp.org/doc/WaveFormat/
-fn prepend_wav_header(bytes: &[u8]) -> Result<Vec<u8>> {
-    let mut res = Vec::with_capacity(bytes.len() + 44);
+pub fn prepend_wav_header(bytes: &[u8], format: &RawSoundFormat) -> Result<Vec<u8>> {
+    format.validate()?;
 
-    let block_align: u16 = NUM_CHANNELS * (BITS_PER_SAMPLE / 8);
-    let byte_rate: u32 = SAMPLE_RATE * block_align as u32;
+    let block_align = format.block_align();
+    let bytes = &bytes[..bytes.len() / block_align * block_align];
+    let data_size: u32 = bytes
+        .len()
+        .try_into()
+        .context("Too many samples for a WAV file")?;
+    let byte_rate: u32 = format.sample_rate * block_align as u32;
+
+    let mut res = Vec::with_capacity(bytes.len() + 44);
 
     // ChunkID
     res.extend_from_slice(b"RIFF");
     // ChunkSize: file size - 8 bytes
-    res.write_u32::<LE>(36 + bytes.len() as u32)?;
+    res.write_u32::<LE>(data_size.saturating_add(36))?;
     // Format
     res.extend_from_slice(b"WAVE");
     // Subchunk1ID
@@ -58,23 +250,76 @@ fn prepend_wav_header(bytes: &[u8]) -> Result<Vec<u8>> {
     res.write_u32::<LE>(16)?;
     // AudioFormat: 1 = PCM
     res.write_u16::<LE>(1)?;
-    // NumChannels: 2
-    res.write_u16::<LE>(NUM_CHANNELS)?;
-    // SampleRate: 44100
-    res.write_u32::<LE>(SAMPLE_RATE)?;
+    // NumChannels
+    res.write_u16::<LE>(format.channels)?;
+    // SampleRate
+    res.write_u32::<LE>(format.sample_rate)?;
     // ByteRate: SampleRate * NumChannels * BitsPerSample / 8
     res.write_u32::<LE>(byte_rate)?;
     // BlockAlign: NumChannels * BitsPerSample / 8
-    res.write_u16::<LE>(block_align)?;
+    res.write_u16::<LE>(block_align as u16)?;
     // BitsPerSample
-    res.write_u16::<LE>(BITS_PER_SAMPLE)?;
+    res.write_u16::<LE>(format.bits_per_sample)?;
 
     // SubChunk2ID
     res.extend_from_slice(b"data");
     // Subchunk2Size
-    res.write_u32::<LE>(bytes.len() as u32)?;
+    res.write_u32::<LE>(data_size)?;
     // Samples
-    res.extend_from_slice(bytes);
+    match (format.bits_per_sample, format.signed) {
+        (8, false) | (16, true) => res.extend_from_slice(bytes),
+        (8, true) => res.extend(bytes.iter().map(|v| v ^ 0x80)),
+        _ => res.extend(bytes.chunks_exact(2).flat_map(|v| [v[0], v[1] ^ 0x80])),
+    }
 
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_overrides_some_fields() {
+        let format = parse_format(br#"{ "sample_rate": 11025, "channels": 1 }"#).unwrap();
+        assert_eq!(format.sample_rate, 11025);
+        assert_eq!(format.channels, 1);
+        assert_eq!(format.bits_per_sample, 16);
+
+        assert!(parse_format(br#"{ "bits_per_sample": 24 }"#).is_err());
+        assert!(parse_format(br#"{ "rate": 11025 }"#).is_err());
+    }
+
+    #[test]
+    fn decodes_every_format() {
+        let bytes: Arc<[u8]> = vec![0x00, 0x80, 0xFF, 0x7F, 0x01].into();
+        let decode = |bits_per_sample, signed| {
+            let format = RawSoundFormat {
+                sample_rate: 8000,
+                channels: 1,
+                bits_per_sample,
+                signed,
+            };
+            let sound = RawSound {
+                bytes: bytes.clone(),
+                format,
+            };
+            sound.samples().collect::<Vec<i16>>()
+        };
+
+        assert_eq!(decode(16, true), vec![-32768, 32767]);
+        assert_eq!(decode(16, false), vec![0, -1]);
+        assert_eq!(decode(8, true), vec![0, -32768, -256, 32512, 256]);
+        assert_eq!(decode(8, false), vec![-32768, 0, 32512, -256, -32512]);
+    }
+
+    #[test]
+    fn wav_header_matches_format() {
+        let format = RawSoundFormat::default();
+        let wav = prepend_wav_header(&[1, 2, 3, 4, 5], &format).unwrap();
+        assert_eq!(wav.len(), 44 + 4);
+        assert_eq!(&wav[22..24], &2u16.to_le_bytes());
+        assert_eq!(&wav[24..28], &22000u32.to_le_bytes());
+        assert_eq!(&wav[40..44], &4u32.to_le_bytes());
+    }
+}
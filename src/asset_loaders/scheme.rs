@@ -12,7 +12,7 @@ use std::io::BufReader;
 #[derive(Default)]
 pub struct SchemeAssetLoader;
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Powerup {
     ExtraBomb = 0,
     LongerFlame = 1,
@@ -77,7 +77,7 @@ impl AssetLoader for SchemeAssetLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<()>> {
         Box::pin(async move {
-            load_context.set_default_asset(LoadedAsset::new(parse(bytes)?));
+            load_context.set_default_asset(LoadedAsset::new(parse_scheme(bytes)?));
             Ok(())
         })
     }
@@ -87,7 +87,7 @@ impl AssetLoader for SchemeAssetLoader {
     }
 }
 
-fn parse(bytes: &[u8]) -> Result<Scheme> {
+pub fn parse_scheme(bytes: &[u8]) -> Result<Scheme> {
     let mut reader = BufReader::new(bytes);
     let mut scheme = Scheme::new();
 
@@ -0,0 +1,56 @@
+use anyhow::{Context, Result};
+use bevy::asset::LoadContext;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+
+/// Paths of the JSON sidecars that may configure the file at `path`, in the
+/// order of precedence: the sidecar named after the file with a `.json`
+/// suffix, e.g. `MENU.RSS.json`, then `directory_sidecar` in its directory,
+/// which configures all files of that directory.
+pub fn sidecar_paths(path: &Path, directory_sidecar: &str) -> [PathBuf; 2] {
+    let mut file_sidecar = path.as_os_str().to_owned();
+    file_sidecar.push(".json");
+    let directory_sidecar = path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(directory_sidecar);
+
+    [file_sidecar.into(), directory_sidecar]
+}
+
+/// Reads the settings of the file that is being loaded from its sidecar, see
+/// `sidecar_paths`, or the default settings without one.
+pub(super) async fn read_sidecar<T: DeserializeOwned + Default>(
+    load_context: &LoadContext<'_>,
+    directory_sidecar: &str,
+) -> Result<T> {
+    for sidecar in sidecar_paths(load_context.path(), directory_sidecar) {
+        // A sidecar that can't be read doesn't exist.
+        if let Ok(bytes) = load_context.read_asset_bytes(&sidecar).await {
+            return serde_json::from_slice(&bytes)
+                .with_context(|| format!("Reading {:?}", sidecar));
+        }
+    }
+
+    Ok(T::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_sidecar_comes_first() {
+        assert_eq!(
+            sidecar_paths(Path::new("data/RES/MAINMENU.PCX"), "pcx.json"),
+            [
+                PathBuf::from("data/RES/MAINMENU.PCX.json"),
+                PathBuf::from("data/RES/pcx.json")
+            ]
+        );
+        assert_eq!(
+            sidecar_paths(Path::new("MENU.RSS"), "rss.json"),
+            [PathBuf::from("MENU.RSS.json"), PathBuf::from("rss.json")]
+        );
+    }
+}
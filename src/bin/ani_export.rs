@@ -0,0 +1,111 @@
+//! Exports the frames of an ANI file as PNG images, with a JSON manifest that
+//! describes the sequences playing them.
+//!
+//! Usage: ani_export <FILE.ANI> <OUTPUT_DIR>
+
+use anyhow::{bail, Context, Result};
+use atomic_bomberman::animation::{
+    Decoder, Manifest, ManifestFrame, ManifestSequence, ManifestSequenceFrame,
+};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        bail!("Usage: {} <FILE.ANI> <OUTPUT_DIR>", args[0]);
+    }
+
+    export(Path::new(&args[1]), Path::new(&args[2]))
+}
+
+fn export(source: &Path, output: &Path) -> Result<()> {
+    let bytes = fs::read(source).with_context(|| format!("Reading {:?}", source))?;
+    let bundle = Decoder::new(&bytes)
+        .and_then(|mut decoder| decoder.load_animation_bundle())
+        .with_context(|| format!("Decoding {:?}", source))?;
+
+    fs::create_dir_all(output).with_context(|| format!("Creating {:?}", output))?;
+
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "frame".into());
+
+    let mut frames = vec![];
+    for (index, rect) in bundle.rects.iter().enumerate() {
+        let width = (rect.max.x - rect.min.x) as u32;
+        let height = (rect.max.y - rect.min.y) as u32;
+
+        let mut file = None;
+        if width > 0 && height > 0 {
+            let name = format!("{}_{:03}.png", stem, index);
+            let pixels = bundle.frame_pixels(index).unwrap();
+            write_png(&output.join(&name), width, height, &pixels)?;
+            file = Some(name);
+        }
+
+        let (hotspot_x, hotspot_y) = bundle.hotspots[index];
+        frames.push(ManifestFrame {
+            index,
+            file,
+            width,
+            height,
+            hotspot_x,
+            hotspot_y,
+        });
+    }
+
+    let sequences = bundle
+        .animations
+        .iter()
+        .map(|animation| ManifestSequence {
+            name: animation.name.clone(),
+            frames: animation
+                .frames
+                .iter()
+                .map(|frame| ManifestSequenceFrame {
+                    frame: frame.index,
+                    offset_x: frame.offset_x,
+                    offset_y: frame.offset_y,
+                    duration_ms: frame.duration.as_millis() as u64,
+                })
+                .collect(),
+        })
+        .collect();
+
+    let manifest = Manifest {
+        source: source.to_string_lossy().into_owned(),
+        header: bundle.header.clone(),
+        collision_box: bundle.collision_box.map(Into::into),
+        frames,
+        sequences,
+    };
+
+    let path: PathBuf = output.join(format!("{}.json", stem));
+    let writer =
+        BufWriter::new(File::create(&path).with_context(|| format!("Creating {:?}", path))?);
+    serde_json::to_writer_pretty(writer, &manifest)?;
+
+    println!(
+        "exported {} frames and {} sequences to {:?}",
+        manifest.frames.len(),
+        manifest.sequences.len(),
+        output
+    );
+
+    Ok(())
+}
+
+fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Creating {:?}", path))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    Ok(())
+}
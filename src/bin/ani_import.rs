@@ -0,0 +1,119 @@
+//! Builds an ANI file from PNG frames and a JSON manifest, in the layout
+//! written by `ani_export`.
+//!
+//! Usage: ani_import <MANIFEST.json> <FILE.ANI>
+
+use anyhow::{bail, Context, Result};
+use atomic_bomberman::animation::{
+    Encoder, EncoderFrame, EncoderSequence, EncoderSequenceFrame, Manifest,
+};
+use std::{fs, fs::File, path::Path, time::Duration};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        bail!("Usage: {} <MANIFEST.json> <FILE.ANI>", args[0]);
+    }
+
+    import(Path::new(&args[1]), Path::new(&args[2]))
+}
+
+fn import(manifest_path: &Path, output: &Path) -> Result<()> {
+    let file = File::open(manifest_path).with_context(|| format!("Opening {:?}", manifest_path))?;
+    let manifest: Manifest =
+        serde_json::from_reader(file).with_context(|| format!("Parsing {:?}", manifest_path))?;
+    let dir = manifest_path.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut frames = vec![];
+    for (index, frame) in manifest.frames.iter().enumerate() {
+        if frame.index != index {
+            bail!("Frame {} is listed at position {}", frame.index, index);
+        }
+
+        let (name, width, height, pixels) = match &frame.file {
+            Some(file) => {
+                let (width, height, pixels) = read_png(&dir.join(file))?;
+                (file.clone(), width, height, pixels)
+            }
+            None => (String::new(), 0, 0, vec![]),
+        };
+
+        frames.push(EncoderFrame {
+            name,
+            width,
+            height,
+            hotspot_x: frame.hotspot_x,
+            hotspot_y: frame.hotspot_y,
+            pixels,
+        });
+    }
+
+    let sequences = manifest
+        .sequences
+        .iter()
+        .map(|sequence| EncoderSequence {
+            name: sequence.name.clone(),
+            frames: sequence
+                .frames
+                .iter()
+                .map(|frame| EncoderSequenceFrame {
+                    frame: frame.frame,
+                    offset_x: frame.offset_x,
+                    offset_y: frame.offset_y,
+                    duration: Duration::from_millis(frame.duration_ms),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let encoder = Encoder {
+        header: manifest.header,
+        collision_box: manifest.collision_box.map(Into::into),
+        frames,
+        sequences,
+    };
+
+    let data = encoder
+        .encode()
+        .with_context(|| format!("Encoding {:?}", manifest_path))?;
+    fs::write(output, data).with_context(|| format!("Writing {:?}", output))?;
+
+    println!(
+        "imported {} frames and {} sequences into {:?}",
+        encoder.frames.len(),
+        encoder.sequences.len(),
+        output
+    );
+
+    Ok(())
+}
+
+/// Reads a PNG of any color type as RGBA pixels.
+fn read_png(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let file = File::open(path).with_context(|| format!("Opening {:?}", path))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder
+        .read_info()
+        .with_context(|| format!("Reading {:?}", path))?;
+
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf)?;
+
+    let (color_type, _) = reader.output_color_type();
+    let pixels = match color_type {
+        png::ColorType::RGBA => buf,
+        png::ColorType::RGB => buf
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|v| [*v, *v, *v, 255]).collect(),
+        png::ColorType::Indexed => bail!("{:?}: indexed colors were not expanded", path),
+    };
+
+    Ok((info.width, info.height, pixels))
+}
@@ -0,0 +1,92 @@
+//! Bundles the `data/` and `fonts/` directories of an asset folder into one
+//! pack file, which the game reads instead of the loose files when it is
+//! placed in the asset folder as `data.pak`.
+//!
+//! Usage: pack <ASSET_DIR> <OUTPUT.pak>
+//!
+//! ANI files are stored decoded, with their packed atlas, so the game doesn't
+//! decode them on startup. Files that fail to decode are stored as they are.
+
+use anyhow::{bail, Context, Result};
+use atomic_bomberman::{animation::Decoder, asset_io::PackWriter};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Directories of the asset folder that go into the pack.
+const DIRECTORIES: &[&str] = &["data", "fonts"];
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        bail!("Usage: {} <ASSET_DIR> <OUTPUT.pak>", args[0]);
+    }
+    let asset_dir = Path::new(&args[1]);
+    let output = Path::new(&args[2]);
+
+    let mut files = vec![];
+    for dir in DIRECTORIES {
+        let dir = asset_dir.join(dir);
+        if dir.is_dir() {
+            walk(&dir, &mut files)?;
+        } else {
+            println!("{}: skipped, not a directory", dir.display());
+        }
+    }
+    files.sort();
+
+    let mut writer = PackWriter::default();
+    let mut decoded = 0;
+    for path in &files {
+        let relative = path.strip_prefix(asset_dir)?;
+        let name = relative
+            .to_str()
+            .with_context(|| format!("{:?} is not valid UTF-8", relative))?
+            .replace('\\', "/");
+
+        let mut bytes = fs::read(path).with_context(|| format!("Reading {:?}", path))?;
+        if is_animation(path) {
+            match decode_animation(&bytes) {
+                Ok(decoded_bytes) => {
+                    bytes = decoded_bytes;
+                    decoded += 1;
+                }
+                Err(err) => println!("{}: warning: stored undecoded: {}", path.display(), err),
+            }
+        }
+
+        writer.add(&name, bytes)?;
+    }
+
+    writer.write(output)?;
+    println!(
+        "packed {} files into {}, {} animations decoded",
+        writer.len(),
+        output.display(),
+        decoded
+    );
+
+    Ok(())
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Reading {:?}", dir))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_animation(path: &Path) -> bool {
+    matches!(path.extension(), Some(ext) if ext.eq_ignore_ascii_case("ani"))
+}
+
+fn decode_animation(bytes: &[u8]) -> Result<Vec<u8>> {
+    let bundle = Decoder::new(bytes)?.load_animation_bundle()?;
+    Ok(bundle.to_bytes())
+}
@@ -0,0 +1,153 @@
+//! Converts every RSS file in a directory to a WAV file, to listen to the
+//! sounds without starting the game.
+//!
+//! Usage: rss_to_wav [--normalize] [--trim-silence] <INPUT_DIR> <OUTPUT_DIR>
+//!
+//! The sample format is read from the same sidecar files as the game uses.
+//! `--normalize` scales every file to full volume, `--trim-silence` removes
+//! silence at the start and end.
+
+use anyhow::{bail, Context, Result};
+use atomic_bomberman::asset_loaders::{
+    parse_format, prepend_wav_header, sidecar_paths, RawSound, RawSoundFormat, RSS_SIDECAR,
+};
+use std::{fs, path::Path};
+
+/// Samples below this amplitude count as silence, about -48dB.
+const SILENCE_THRESHOLD: i16 = 128;
+
+#[derive(Default)]
+struct Options {
+    normalize: bool,
+    trim_silence: bool,
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let usage = format!(
+        "Usage: {} [--normalize] [--trim-silence] <INPUT_DIR> <OUTPUT_DIR>",
+        args[0]
+    );
+
+    let mut options = Options::default();
+    let mut paths = vec![];
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "--normalize" => options.normalize = true,
+            "--trim-silence" => options.trim_silence = true,
+            flag if flag.starts_with("--") => bail!("Unknown option {}\n{}", flag, usage),
+            path => paths.push(Path::new(path)),
+        }
+    }
+    if paths.len() != 2 {
+        bail!(usage);
+    }
+
+    convert_dir(paths[0], paths[1], &options)
+}
+
+fn convert_dir(input: &Path, output: &Path, options: &Options) -> Result<()> {
+    fs::create_dir_all(output).with_context(|| format!("Creating {:?}", output))?;
+
+    let mut entries: Vec<_> = fs::read_dir(input)
+        .with_context(|| format!("Reading {:?}", input))?
+        .collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut converted = 0;
+    for entry in entries {
+        let path = entry.path();
+        let is_rss = matches!(path.extension(), Some(ext) if ext.eq_ignore_ascii_case("rss"));
+        if !is_rss || !path.is_file() {
+            continue;
+        }
+
+        let stem = path.file_stem().unwrap().to_string_lossy().to_lowercase();
+        let target = output.join(format!("{}.wav", stem));
+        convert(&path, &target, options).with_context(|| format!("Converting {:?}", path))?;
+        converted += 1;
+    }
+
+    println!("converted {} files to {:?}", converted, output);
+
+    Ok(())
+}
+
+fn convert(path: &Path, target: &Path, options: &Options) -> Result<()> {
+    let format = read_format(path)?;
+    let bytes = fs::read(path)?;
+
+    let wav = if options.normalize || options.trim_silence {
+        let sound = RawSound {
+            bytes: bytes.into(),
+            format,
+        };
+        let mut samples: Vec<i16> = sound.samples().collect();
+
+        if options.trim_silence {
+            samples = trim_silence(&samples, format.channels as usize).to_vec();
+        }
+        if options.normalize {
+            normalize(&mut samples);
+        }
+
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let format = RawSoundFormat {
+            bits_per_sample: 16,
+            signed: true,
+            ..format
+        };
+        prepend_wav_header(&bytes, &format)?
+    } else {
+        prepend_wav_header(&bytes, &format)?
+    };
+
+    fs::write(target, wav).with_context(|| format!("Writing {:?}", target))
+}
+
+/// Format from the sidecar of the file or of its directory, like
+/// `RawSoundAssetLoader`.
+fn read_format(path: &Path) -> Result<RawSoundFormat> {
+    for sidecar in sidecar_paths(path, RSS_SIDECAR) {
+        if sidecar.is_file() {
+            let bytes = fs::read(&sidecar)?;
+            return parse_format(&bytes).with_context(|| format!("Reading {:?}", sidecar));
+        }
+    }
+
+    Ok(RawSoundFormat::default())
+}
+
+/// Removes the frames at the start and end in which every channel is below
+/// `SILENCE_THRESHOLD`.
+fn trim_silence(samples: &[i16], channels: usize) -> &[i16] {
+    let loud = |frame: &[i16]| {
+        frame
+            .iter()
+            .any(|s| s.unsigned_abs() >= SILENCE_THRESHOLD as u16)
+    };
+    let frames: Vec<&[i16]> = samples.chunks(channels).collect();
+
+    let start = match frames.iter().position(|frame| loud(frame)) {
+        Some(start) => start,
+        None => return &[],
+    };
+    let end = frames.iter().rposition(|frame| loud(frame)).unwrap() + 1;
+
+    &samples[start * channels..end * channels]
+}
+
+/// Scales the samples so the loudest one is at full volume.
+fn normalize(samples: &mut [i16]) {
+    let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+    if peak == 0 {
+        return;
+    }
+
+    let gain = i16::MAX as f32 / peak as f32;
+    for sample in samples.iter_mut() {
+        *sample = (*sample as f32 * gain)
+            .round()
+            .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    }
+}
@@ -0,0 +1,233 @@
+//! Decodes every file of a data directory with the loaders of the game and
+//! reports the files that fail or look suspicious, without starting the game.
+//! Files that are listed in MASTER.ALI or SOUNDLST.RES but missing, or
+//! present but not listed, are reported as well.
+//!
+//! Usage: validate_data <DATA_DIR>
+
+use anyhow::{anyhow, bail, Context, Result};
+use atomic_bomberman::{
+    animation::Decoder,
+    asset_loaders::{
+        parse_file_list, parse_format, parse_scheme, sidecar_paths, ColorPalette, ColorRemapTable,
+        PcxDecoder, RawSoundFormat, RSS_SIDECAR,
+    },
+    loading::{AssetList, ANIMATION_LIST, SOUND_LIST},
+};
+use std::{
+    collections::HashSet,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+#[derive(Default)]
+struct Report {
+    files: usize,
+    errors: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn error(&mut self, path: &Path, message: impl std::fmt::Display) {
+        println!("{}: error: {}", path.display(), message);
+        self.errors += 1;
+    }
+
+    fn warning(&mut self, path: &Path, message: impl std::fmt::Display) {
+        println!("{}: warning: {}", path.display(), message);
+        self.warnings += 1;
+    }
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 2 {
+        bail!("Usage: {} <DATA_DIR>", args[0]);
+    }
+    let data_dir = Path::new(&args[1]);
+
+    let mut files = vec![];
+    walk(data_dir, &mut files)?;
+    files.sort();
+
+    let mut report = Report::default();
+    for path in &files {
+        validate_file(path, &mut report);
+    }
+    check_list(data_dir, &ANIMATION_LIST, &mut report)?;
+    check_list(data_dir, &SOUND_LIST, &mut report)?;
+
+    println!(
+        "checked {} files: {} errors, {} warnings",
+        report.files, report.errors, report.warnings
+    );
+
+    if report.errors > 0 {
+        bail!("found {} errors", report.errors);
+    }
+    Ok(())
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Reading {:?}", dir))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_uppercase())
+}
+
+fn validate_file(path: &Path, report: &mut Report) {
+    let validate: fn(&Path, &[u8]) -> Result<Vec<String>> = match extension(path).as_deref() {
+        Some("ANI") => validate_animation,
+        Some("PCX") => validate_image,
+        Some("RSS") => validate_sound,
+        Some("SCH") => |_, bytes| parse_scheme(bytes).map(|_| vec![]),
+        Some("PAL") => |_, bytes| ColorPalette::new(bytes).map(|_| vec![]),
+        Some("RMP") => |_, bytes| ColorRemapTable::new(bytes).map(|_| vec![]),
+        _ => return,
+    };
+    report.files += 1;
+
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => return report.error(path, err),
+    };
+
+    match catch_panic(|| validate(path, &bytes)) {
+        Ok(warnings) => {
+            for warning in warnings {
+                report.warning(path, warning);
+            }
+        }
+        Err(err) => report.error(path, err),
+    }
+}
+
+/// Runs `validate`, decoders that panic on malformed data are reported as
+/// errors that name where they panicked, instead of printing the panic.
+fn catch_panic(validate: impl FnOnce() -> Result<Vec<String>>) -> Result<Vec<String>> {
+    let location = Arc::new(Mutex::new(None));
+    let hook_location = location.clone();
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        *hook_location.lock().unwrap() = info.location().map(|l| l.to_string());
+    }));
+    let result = panic::catch_unwind(AssertUnwindSafe(validate));
+    panic::set_hook(default_hook);
+
+    result.unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        let location = location.lock().unwrap().take().unwrap_or_default();
+        bail!("decoder panicked at {}: {}", location, message)
+    })
+}
+
+fn validate_animation(_: &Path, bytes: &[u8]) -> Result<Vec<String>> {
+    let mut decoder = Decoder::new(bytes)?;
+    let bundle = decoder.load_animation_bundle()?;
+
+    let mut warnings: Vec<String> = decoder.warnings().iter().map(|w| w.to_string()).collect();
+    if bundle.animations.is_empty() {
+        warnings.push("no sequences".into());
+    }
+    Ok(warnings)
+}
+
+fn validate_image(_: &Path, bytes: &[u8]) -> Result<Vec<String>> {
+    PcxDecoder::new(bytes)?.read_image_data()?;
+    Ok(vec![])
+}
+
+fn validate_sound(path: &Path, bytes: &[u8]) -> Result<Vec<String>> {
+    let mut format = RawSoundFormat::default();
+    for sidecar in sidecar_paths(path, RSS_SIDECAR) {
+        if sidecar.is_file() {
+            format = parse_format(&fs::read(&sidecar)?)
+                .map_err(|err| anyhow!("{:?}: {}", sidecar, err))?;
+            break;
+        }
+    }
+
+    let mut warnings = vec![];
+    let block_align = format.block_align();
+    let trailing = bytes.len() % block_align;
+    if bytes.is_empty() {
+        warnings.push("no samples".into());
+    } else if trailing > 0 {
+        warnings.push(format!(
+            "{} trailing bytes don't fill a {} byte sample",
+            trailing, block_align
+        ));
+    }
+    Ok(warnings)
+}
+
+/// Compares the files the game loads with `list` to the ones on disk,
+/// ignoring case.
+fn check_list(data_dir: &Path, list: &AssetList, report: &mut Report) -> Result<()> {
+    let ext = list.extension.to_uppercase();
+    let list_path = in_data_dir(data_dir, list.list)?;
+    let dir = in_data_dir(data_dir, list.dir)?;
+    let list = match fs::read(&list_path)
+        .map_err(Into::into)
+        .and_then(|bytes| parse_file_list(&bytes))
+    {
+        Ok(list) => list,
+        Err(err) => {
+            report.error(&list_path, err);
+            return Ok(());
+        }
+    };
+    let list_name = list_path.file_name().unwrap().to_string_lossy();
+
+    if !dir.is_dir() {
+        report.error(&dir, "directory is missing");
+        return Ok(());
+    }
+
+    let mut on_disk = HashSet::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("Reading {:?}", dir))? {
+        let path = entry?.path();
+        if extension(&path).as_deref() == Some(ext.as_str()) {
+            let name = path.file_name().unwrap().to_string_lossy().to_uppercase();
+            if !list.contains(&name) {
+                report.warning(&path, format!("not in {}", list_name));
+            }
+            on_disk.insert(name);
+        }
+    }
+
+    for name in list.with_extension(&ext) {
+        if !on_disk.contains(&name.to_uppercase()) {
+            report.error(
+                &dir.join(name),
+                format!("listed in {} but missing", list_name),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Path of an asset of the game in `data/` in the data directory.
+fn in_data_dir(data_dir: &Path, asset_path: &str) -> Result<PathBuf> {
+    let relative = Path::new(asset_path)
+        .strip_prefix("data")
+        .with_context(|| format!("{:?} is not in the data directory", asset_path))?;
+    Ok(data_dir.join(relative))
+}
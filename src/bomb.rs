@@ -2,6 +2,7 @@ use crate::{
     animation::*,
     asset_loaders::*,
     grid::{Direction, *},
+    player::PlayerId,
     state::*,
 };
 use bevy::prelude::*;
@@ -22,12 +23,20 @@ impl Plugin for BombPlugin {
     }
 }
 
+/// Parent of all flame cells that originate from the bombs of one player.
 #[derive(Component)]
-pub struct Flame;
+pub struct Flame {
+    pub owner: PlayerId,
+}
+/// A single cell of a `Flame`, spawned as its child.
+#[derive(Component)]
+pub struct FlamePart;
 #[derive(Component)]
-pub struct Bomb;
+pub struct Bomb {
+    pub owner: PlayerId,
+}
 #[derive(Component)]
-pub struct PlaceBombEvent(pub Position);
+pub struct PlaceBombEvent(pub Position, pub PlayerId);
 #[derive(Component)]
 pub struct FlameBrick;
 
@@ -51,17 +60,13 @@ fn place_bomb(
     animation_assets: Res<Assets<Animation>>,
     mut place_bomb_events: EventReader<PlaceBombEvent>,
     query: Query<&Position, With<Bomb>>,
-    audio: Res<Audio>,
+    audio: Res<Audio<RawSound>>,
 ) {
     for event in place_bomb_events.iter() {
         if !query.iter().any(|&p| p == event.0) {
-            let animation = named_assets.animations.get("bomb regular green").unwrap();
-            let asset = animation_assets.get(animation).unwrap();
-            let offset = Vec2::new(
-                ((40 - asset.width as i32) / 2) as f32,
-                ((36 - asset.height as i32) / 2) as f32 * -1.0,
-            );
-            println!("bomb offset = {:?}", offset);
+            let animation = named_assets
+                .colored_animation("bomb regular green", event.1.color())
+                .unwrap();
 
             commands
                 .spawn_bundle(AnimatedSpriteBundle::new(
@@ -69,10 +74,11 @@ fn place_bomb(
                     &animation_assets,
                     Default::default(),
                 ))
-                .insert(Bomb)
+                .insert(Bomb { owner: event.1 })
                 .insert(event.0)
-                .insert(Offset(Vec3::from((offset, 25.0))))
+                .insert(Offset(Vec3::ZERO))
                 .insert(SnapToGrid)
+                .insert(Layer::Bomb)
                 .insert(BombTimer{ timer: Timer::from_seconds(3.0, false) });
 
             let handle = named_assets.sounds.get("bmdrop2").unwrap();
@@ -86,9 +92,9 @@ fn trigger_bomb(
     named_assets: Res<NamedAssets>,
     animation_assets: Res<Assets<Animation>>,
     time: Res<Time>,
-    mut query: Query<(Entity, &Position, &mut BombTimer), With<Bomb>>,
+    mut query: Query<(Entity, &Position, &mut BombTimer, &Bomb)>,
     grid: Query<(&Position, Entity, &Cell)>,
-    audio: Res<Audio>,
+    audio: Res<Audio<RawSound>>,
 ) {
     let grid: HashMap<&Position, (Entity, &Cell)> = grid
         .iter()
@@ -96,12 +102,12 @@ fn trigger_bomb(
         .collect();
 
     let mut bombs: HashSet<Position> = HashSet::new();
-    let mut new_bombs: Vec<Position> = vec![];
-    for (entity, pos, mut timer) in query.iter_mut() {
+    let mut new_bombs: Vec<(Position, PlayerId)> = vec![];
+    for (entity, pos, mut timer, bomb) in query.iter_mut() {
         timer.timer.tick(time.delta());
         if timer.timer.finished() {
             bombs.insert(*pos);
-            new_bombs.push(*pos);
+            new_bombs.push((*pos, bomb.owner));
             commands.entity(entity).despawn_recursive();
             break;
         }
@@ -115,79 +121,77 @@ fn trigger_bomb(
     audio.play(handle.clone());
 
     let mut flame: HashMap<Position, FlameCell> = HashMap::new();
+    // A cell is credited to the owner of the bomb whose flame reached it first,
+    // chained bombs keep their own owner.
+    let mut owners: HashMap<Position, PlayerId> = HashMap::new();
 
     let flamebrick = named_assets.animations.get("flame brick 0").unwrap();
 
-    while let Some(pos) = new_bombs.pop() {
+    while let Some((pos, owner)) = new_bombs.pop() {
         let strength = 3;
         let bricks = trace_flame(pos, strength, &grid, &mut flame);
+        for pos in flame.keys() {
+            owners.entry(*pos).or_insert(owner);
+        }
+
         for (entity, pos) in bricks.iter() {
             println!("destroying brick at {:?}", pos);
             commands.entity(*entity).despawn_recursive();
 
             commands
-                .spawn_bundle(AnimatedSpriteBundle::new(
-                    flamebrick.clone(),
-                    &animation_assets,
-                    Default::default(),
-                ))
+                .spawn_bundle(
+                    AnimatedSpriteBundle::new(
+                        flamebrick.clone(),
+                        &animation_assets,
+                        Default::default(),
+                    )
+                    .with_mode(PlaybackMode::Once),
+                )
                 .insert(*pos)
-                .insert(Offset(Vec3::new(0.0, 0.0, 25.0)))
+                .insert(Offset(Vec3::ZERO))
                 .insert(SnapToGrid)
-                .insert(FlameBrick)
-                .insert(BombTimer{timer: Timer::from_seconds(0.25, false)});
+                .insert(Layer::Block)
+                .insert(FlameBrick);
 
             // TODO: trigger event? spawn potential upgrades?
         }
 
-        for (entity, pos, _timer) in query.iter_mut() {
+        for (entity, pos, _timer, bomb) in query.iter_mut() {
             if !bombs.contains(pos) && flame.contains_key(pos) {
                 println!("trigger another bomb at {:?}", pos);
                 bombs.insert(*pos);
-                new_bombs.push(*pos);
+                new_bombs.push((*pos, bomb.owner));
                 commands.entity(entity).despawn_recursive();
             }
         }
     }
 
-    let center = named_assets.animations.get("flame center green").unwrap();
-    let midwest = named_assets.animations.get("flame midwest green").unwrap();
-    let tipwest = named_assets.animations.get("flame tipwest green").unwrap();
-    let mideast = named_assets.animations.get("flame mideast green").unwrap();
-    let tipeast = named_assets.animations.get("flame tipeast green").unwrap();
-    let midnorth = named_assets.animations.get("flame midnorth green").unwrap();
-    let tipnorth = named_assets.animations.get("flame tipnorth green").unwrap();
-    let midsouth = named_assets.animations.get("flame midsouth green").unwrap();
-    let tipsouth = named_assets.animations.get("flame tipsouth green").unwrap();
-
-    let asset = animation_assets.get(midwest).unwrap();
-    println!("{:?}", asset);
-
     // TODO: is it possible to spawn an "empty" parent?
-    let flame_entity = commands
-        .spawn_bundle((Transform::default(), GlobalTransform::default()))
-        .insert(Flame)
-        .insert(BombTimer{timer: Timer::from_seconds(0.75, false)})
-        .id();
+    let mut flame_entities: HashMap<PlayerId, Entity> = HashMap::new();
 
     for (pos, cell) in flame.iter() {
-        let (animation, offset) = match cell {
-            FlameCell::North => (midnorth, Vec2::new(7.0, 0.0)),
-            FlameCell::East => (midwest, Vec2::new(0.0, -7.0)),
-            FlameCell::South => (midsouth, Vec2::new(7.0, 0.0)),
-            FlameCell::West => (mideast, Vec2::new(0.0, -7.0)),
-            FlameCell::Center => (center, Vec2::new(0.0, 0.0)),
-        };
+        let owner = owners[pos];
+        let flame_entity = *flame_entities.entry(owner).or_insert_with(|| {
+            commands
+                .spawn_bundle((Transform::default(), GlobalTransform::default()))
+                .insert(Flame { owner })
+                .id()
+        });
+
+        let animation = named_assets
+            .colored_animation(cell.animation(), owner.color())
+            .unwrap();
 
         let part = commands
-            .spawn_bundle(AnimatedSpriteBundle::new(
-                animation.clone(),
-                &animation_assets,
-                Default::default(),
-            ))
+            .spawn_bundle(
+                AnimatedSpriteBundle::new(animation.clone(), &animation_assets, Default::default())
+                    .with_mode(PlaybackMode::Once),
+            )
             .insert(*pos)
-            .insert(Offset(Vec3::from((offset, 25.0))))
+            .insert(Offset(Vec3::ZERO))
             .insert(SnapToGrid)
+            .insert(Layer::Flame)
+            .insert(FlamePart)
             .id();
 
         commands.entity(flame_entity).push_children(&[part]);
@@ -230,28 +234,31 @@ fn trace_flame(
     bricks
 }
 
+/// The whole flame goes out as soon as one of its cells finished burning.
 fn flame_out(
     mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<(Entity, &mut BombTimer), With<Flame>>,
+    mut finished_events: EventReader<AnimationFinished>,
+    parts: Query<&Parent, With<FlamePart>>,
 ) {
-    for (entity, mut timer) in query.iter_mut() {
-        timer.timer.tick(time.delta());
-        if timer.timer.finished() {
-            commands.entity(entity).despawn_recursive();
-        }
+    let flames: HashSet<Entity> = finished_events
+        .iter()
+        .filter_map(|event| parts.get(event.entity).ok())
+        .map(|parent| parent.0)
+        .collect();
+
+    for entity in flames {
+        commands.entity(entity).despawn_recursive();
     }
 }
 
 fn flame_brick_out(
     mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<(Entity, &mut BombTimer), With<FlameBrick>>,
+    mut finished_events: EventReader<AnimationFinished>,
+    query: Query<(), With<FlameBrick>>,
 ) {
-    for (entity, mut timer) in query.iter_mut() {
-        timer.timer.tick(time.delta());
-        if timer.timer.finished() {
-            commands.entity(entity).despawn_recursive();
+    for event in finished_events.iter() {
+        if query.get(event.entity).is_ok() {
+            commands.entity(event.entity).despawn_recursive();
         }
     }
 }
@@ -268,6 +275,16 @@ impl From<Direction> for FlameCell {
 }
 
 impl FlameCell {
+    fn animation(&self) -> &'static str {
+        match self {
+            FlameCell::North => "flame midnorth green",
+            FlameCell::East => "flame midwest green",
+            FlameCell::South => "flame midsouth green",
+            FlameCell::West => "flame mideast green",
+            FlameCell::Center => "flame center green",
+        }
+    }
+
     fn dir(&self) -> Option<Direction> {
         match self {
             FlameCell::North => Some(Direction::North),
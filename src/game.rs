@@ -2,8 +2,11 @@ use crate::{
     animation::*,
     asset_loaders::*,
     bomb::*,
-    grid::{Direction, *},
+    game_mode::*,
+    grid::*,
     player::*,
+    powerup::*,
+    score::*,
     state::*,
 };
 use bevy::prelude::*;
@@ -20,6 +23,9 @@ impl Plugin for GamePlugin {
             .add_plugin(GridPlugin)
             .add_plugin(PlayerPlugin)
             .add_plugin(BombPlugin)
+            .add_plugin(PowerupPlugin)
+            .add_plugin(ScorePlugin)
+            .add_plugin(GameModePlugin)
             .add_system_set(SystemSet::on_enter(AppState::Game).with_system(setup))
             .add_system_set(
                 SystemSet::on_update(AppState::Game).with_system(player_pos_text),
@@ -36,6 +42,7 @@ fn setup(
     animation_assets: Res<Assets<Animation>>,
     scheme_assets: Res<Assets<Scheme>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    mut game_mode: ResMut<ActiveGameMode>,
 ) {
     // player: STAND.ANI (four perspectives)
     //   WALK.ANI for walking
@@ -50,42 +57,20 @@ fn setup(
     // let music = asset_server.load("data/SOUND/MENU.RSS");
     // audio.play(music);
 
-    let player_direction = PlayerDirection {
-        direction: Direction::North,
-        walking: false,
-    };
-
-    let animation = named_assets
-        .animations
-        .get(&player_direction.animation())
+    let scheme = scheme_assets
+        .get(named_assets.schemes.get("X MARKS THE SPOT (10)").unwrap())
         .unwrap();
+    commands.insert_resource(StartingPowerups::from_scheme(scheme));
 
     let start_pos = Position { x: 7, y: 5 };
-    commands
-        .spawn_bundle((
-            Transform::from_translation(Vec3::from((start_pos.into(), 30.0))),
-            GlobalTransform::default(),
-        ))
-        .insert(Player)
-        .insert(player_direction)
-        .insert(KeyboardMovement::default())
-        .insert(start_pos)
-        .insert(PlayerSpeed{ timer: Timer::from_seconds(1. / 60., true)})
-        .with_children(|parent| {
-            let shadow = named_assets.animations.get("shadow").unwrap();
-
-            parent.spawn_bundle(AnimatedSpriteBundle::new(
-                shadow.clone(),
-                &animation_assets,
-                Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
-            ));
-
-            parent.spawn_bundle(AnimatedSpriteBundle::new(
-                animation.clone(),
-                &animation_assets,
-                Transform::from_translation(Vec3::new(0.0, 35.0, 0.0)),
-            ));
-        });
+    spawn_player(
+        &mut commands,
+        &named_assets,
+        &animation_assets,
+        PlayerId(0),
+        start_pos,
+    );
+    game_mode.start_round(&[(PlayerId(0), start_pos)]);
 
     commands
         .spawn_bundle(Text2dBundle {
@@ -106,10 +91,6 @@ fn setup(
         })
         .insert(PlayerPosText);
 
-    let scheme = scheme_assets
-        .get(named_assets.schemes.get("X MARKS THE SPOT (10)").unwrap())
-        .unwrap();
-
     let brick = named_assets.animations.get("tile 0 brick").unwrap();
     let solid = named_assets.animations.get("tile 0 solid").unwrap();
     let blank = named_assets.animations.get("tile 0 blank").unwrap();
@@ -133,8 +114,12 @@ fn setup(
                     x: x as i32,
                     y: y as i32,
                 })
-                .insert(Offset(Vec3::new(0.0, 0.0, 20.0)))
+                .insert(Offset(Vec3::ZERO))
                 .insert(SnapToGrid)
+                .insert(match cell {
+                    Cell::Blank => Layer::Floor,
+                    _ => Layer::Block,
+                })
                 .insert(cell);
         }
     }
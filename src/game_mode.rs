@@ -0,0 +1,199 @@
+mod classic;
+mod king_of_the_hill;
+mod lives;
+
+pub use classic::Classic;
+pub use king_of_the_hill::KingOfTheHill;
+pub use lives::Lives;
+
+use crate::{animation::Animation, grid::Position, player::*, score::Scoreboard, state::*};
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// Wires the `ActiveGameMode` into the game. Classic rules are used unless
+/// another mode is inserted as resource before this plugin is added.
+#[derive(Default)]
+pub struct GameModePlugin;
+
+impl Plugin for GameModePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<ActiveGameMode>() {
+            app.insert_resource(ActiveGameMode::new(Classic::default()));
+        }
+
+        app.add_event::<RoundOverEvent>().add_system_set(
+            SystemSet::on_update(AppState::Game)
+                .with_system(handle_deaths)
+                .with_system(update_mode)
+                .with_system(check_round_over)
+                .with_system(show_round_outcome),
+        );
+    }
+}
+
+/// What happens to a player after dying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathOutcome {
+    /// Out for the rest of the round.
+    Eliminated,
+    /// Comes back at the given position, blinking and invulnerable for a
+    /// while.
+    Respawn(Position),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundOutcome {
+    Winner(PlayerId),
+    Draw,
+}
+
+pub struct RoundOverEvent(pub RoundOutcome);
+
+/// Text that announces the outcome of the round.
+#[derive(Component)]
+pub struct RoundOutcomeText;
+
+/// Rules of a game. The hooks are called by the `GameModePlugin` systems, a
+/// mode keeps whatever state it needs to decide the round itself.
+pub trait GameMode: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    /// Called when a round starts, with the players and their start
+    /// positions.
+    fn setup_round(&mut self, players: &[(PlayerId, Position)]);
+
+    /// Called for every player that died.
+    fn on_death(&mut self, event: &PlayerDiedEvent) -> DeathOutcome;
+
+    /// Called every frame with the players that are alive.
+    fn update(&mut self, _delta: Duration, _players: &[(PlayerId, Position)]) {}
+
+    /// The outcome of the round, once it is decided.
+    fn round_outcome(&self) -> Option<RoundOutcome>;
+
+    /// Award points for a finished round. By default the winner gets a win.
+    fn score(&self, outcome: &RoundOutcome, scoreboard: &mut Scoreboard) {
+        if let RoundOutcome::Winner(id) = outcome {
+            scoreboard.round.entry(*id).or_default().wins += 1;
+            scoreboard.total.entry(*id).or_default().wins += 1;
+        }
+    }
+}
+
+pub struct ActiveGameMode {
+    pub mode: Box<dyn GameMode>,
+    round_over: bool,
+}
+
+impl ActiveGameMode {
+    pub fn new(mode: impl GameMode) -> Self {
+        ActiveGameMode {
+            mode: Box::new(mode),
+            round_over: false,
+        }
+    }
+
+    pub fn start_round(&mut self, players: &[(PlayerId, Position)]) {
+        info!("starting {} round", self.mode.name());
+        self.round_over = false;
+        self.mode.setup_round(players);
+    }
+}
+
+/// Last player standing wins, a round in which everybody died is a draw. With
+/// a single participant the round only ends when that player dies.
+fn last_player_standing(participants: usize, alive: &[PlayerId]) -> Option<RoundOutcome> {
+    match alive {
+        [] => Some(RoundOutcome::Draw),
+        [winner] if participants > 1 => Some(RoundOutcome::Winner(*winner)),
+        _ => None,
+    }
+}
+
+fn handle_deaths(
+    mut commands: Commands,
+    named_assets: Res<NamedAssets>,
+    animation_assets: Res<Assets<Animation>>,
+    mut game_mode: ResMut<ActiveGameMode>,
+    mut died_events: EventReader<PlayerDiedEvent>,
+) {
+    for event in died_events.iter() {
+        if let DeathOutcome::Respawn(pos) = game_mode.mode.on_death(event) {
+            debug!("respawning player {:?} at {:?}", event.id, pos);
+            let player = spawn_player(
+                &mut commands,
+                &named_assets,
+                &animation_assets,
+                event.id,
+                pos,
+            );
+            commands
+                .entity(player)
+                .insert(Invulnerable::from_seconds(3.0));
+        }
+    }
+}
+
+fn update_mode(
+    time: Res<Time>,
+    mut game_mode: ResMut<ActiveGameMode>,
+    players: Query<(&PlayerId, &Position), With<Player>>,
+) {
+    let players: Vec<(PlayerId, Position)> =
+        players.iter().map(|(id, pos)| (*id, *pos)).collect();
+    game_mode.mode.update(time.delta(), &players);
+}
+
+fn check_round_over(
+    mut game_mode: ResMut<ActiveGameMode>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut round_over_events: EventWriter<RoundOverEvent>,
+) {
+    if game_mode.round_over {
+        return;
+    }
+
+    if let Some(outcome) = game_mode.mode.round_outcome() {
+        info!("{} round over: {:?}", game_mode.mode.name(), outcome);
+        game_mode.round_over = true;
+        game_mode.mode.score(&outcome, &mut scoreboard);
+        round_over_events.send(RoundOverEvent(outcome));
+    }
+}
+
+fn show_round_outcome(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    scoreboard: Res<Scoreboard>,
+    mut round_over_events: EventReader<RoundOverEvent>,
+) {
+    for RoundOverEvent(outcome) in round_over_events.iter() {
+        let text = match outcome {
+            RoundOutcome::Winner(id) => format!(
+                "Player {} wins the round ({} kills)",
+                id.0 + 1,
+                scoreboard.round_score(*id).kills
+            ),
+            RoundOutcome::Draw => "Draw".to_string(),
+        };
+
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    text,
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 32.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                ),
+                transform: Transform::from_translation(Vec3::new(320., -240., 100.)),
+                ..Default::default()
+            })
+            .insert(RoundOutcomeText);
+    }
+}
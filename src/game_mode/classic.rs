@@ -0,0 +1,30 @@
+use super::*;
+use std::collections::BTreeSet;
+
+/// One life per round, the last player standing wins.
+#[derive(Default)]
+pub struct Classic {
+    participants: usize,
+    alive: BTreeSet<PlayerId>,
+}
+
+impl GameMode for Classic {
+    fn name(&self) -> &'static str {
+        "classic"
+    }
+
+    fn setup_round(&mut self, players: &[(PlayerId, Position)]) {
+        self.participants = players.len();
+        self.alive = players.iter().map(|(id, _pos)| *id).collect();
+    }
+
+    fn on_death(&mut self, event: &PlayerDiedEvent) -> DeathOutcome {
+        self.alive.remove(&event.id);
+        DeathOutcome::Eliminated
+    }
+
+    fn round_outcome(&self) -> Option<RoundOutcome> {
+        let alive: Vec<PlayerId> = self.alive.iter().copied().collect();
+        last_player_standing(self.participants, &alive)
+    }
+}
@@ -0,0 +1,74 @@
+use super::*;
+use crate::grid;
+use std::collections::BTreeMap;
+
+/// Players score by being the only one inside the zone in the center of the
+/// arena. Whoever holds it long enough first wins. Dead players respawn at
+/// their start position.
+pub struct KingOfTheHill {
+    /// Time a player has to hold the zone to win the round.
+    pub hold_duration: Duration,
+    /// Cells that are part of the zone, `radius` cells around the center.
+    pub radius: i32,
+    held: BTreeMap<PlayerId, Duration>,
+    start_positions: BTreeMap<PlayerId, Position>,
+}
+
+const CENTER: Position = Position {
+    x: grid::WIDTH / 2,
+    y: grid::HEIGHT / 2,
+};
+
+impl Default for KingOfTheHill {
+    fn default() -> Self {
+        KingOfTheHill {
+            hold_duration: Duration::from_secs(30),
+            radius: 1,
+            held: BTreeMap::new(),
+            start_positions: BTreeMap::new(),
+        }
+    }
+}
+
+impl KingOfTheHill {
+    pub fn in_zone(&self, pos: &Position) -> bool {
+        (pos.x - CENTER.x).abs() <= self.radius && (pos.y - CENTER.y).abs() <= self.radius
+    }
+
+    pub fn held(&self, player: PlayerId) -> Duration {
+        self.held.get(&player).copied().unwrap_or_default()
+    }
+}
+
+impl GameMode for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "king of the hill"
+    }
+
+    fn setup_round(&mut self, players: &[(PlayerId, Position)]) {
+        self.held.clear();
+        self.start_positions = players.iter().copied().collect();
+    }
+
+    fn on_death(&mut self, event: &PlayerDiedEvent) -> DeathOutcome {
+        match self.start_positions.get(&event.id) {
+            Some(pos) => DeathOutcome::Respawn(*pos),
+            None => DeathOutcome::Eliminated,
+        }
+    }
+
+    fn update(&mut self, delta: Duration, players: &[(PlayerId, Position)]) {
+        // A contested zone doesn't count for anybody.
+        let mut in_zone = players.iter().filter(|(_id, pos)| self.in_zone(pos));
+        if let (Some((id, _pos)), None) = (in_zone.next(), in_zone.next()) {
+            *self.held.entry(*id).or_default() += delta;
+        }
+    }
+
+    fn round_outcome(&self) -> Option<RoundOutcome> {
+        self.held
+            .iter()
+            .find(|(_id, held)| **held >= self.hold_duration)
+            .map(|(id, _held)| RoundOutcome::Winner(*id))
+    }
+}
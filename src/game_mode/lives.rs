@@ -0,0 +1,58 @@
+use super::*;
+use std::collections::BTreeMap;
+
+/// Every player starts with a number of lives and respawns at their start
+/// position until they run out. The last player with lives left wins.
+pub struct Lives {
+    pub lives: u32,
+    participants: usize,
+    remaining: BTreeMap<PlayerId, u32>,
+    start_positions: BTreeMap<PlayerId, Position>,
+}
+
+impl Lives {
+    pub fn new(lives: u32) -> Self {
+        Lives {
+            lives,
+            participants: 0,
+            remaining: BTreeMap::new(),
+            start_positions: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for Lives {
+    fn default() -> Self {
+        Lives::new(3)
+    }
+}
+
+impl GameMode for Lives {
+    fn name(&self) -> &'static str {
+        "lives"
+    }
+
+    fn setup_round(&mut self, players: &[(PlayerId, Position)]) {
+        self.participants = players.len();
+        self.remaining = players.iter().map(|(id, _pos)| (*id, self.lives)).collect();
+        self.start_positions = players.iter().copied().collect();
+    }
+
+    fn on_death(&mut self, event: &PlayerDiedEvent) -> DeathOutcome {
+        let lives = self.remaining.entry(event.id).or_default();
+        *lives = lives.saturating_sub(1);
+
+        match self.start_positions.get(&event.id) {
+            Some(pos) if *lives > 0 => DeathOutcome::Respawn(*pos),
+            _ => {
+                self.remaining.remove(&event.id);
+                DeathOutcome::Eliminated
+            }
+        }
+    }
+
+    fn round_outcome(&self) -> Option<RoundOutcome> {
+        let alive: Vec<PlayerId> = self.remaining.keys().copied().collect();
+        last_player_standing(self.participants, &alive)
+    }
+}
@@ -12,7 +12,9 @@ impl Plugin for GridPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set_to_stage(
             CoreStage::PostUpdate,
-            SystemSet::new().with_system(position_translation),
+            SystemSet::new()
+                .with_system(position_translation.label(GridSystem::Translation))
+                .with_system(y_sort.after(GridSystem::Translation)),
         )
         .add_startup_system(setup)
         .add_system(keyboard_handling);
@@ -45,12 +47,36 @@ pub struct Offset(pub Vec3);
 #[derive(Component)]
 pub struct SnapToGrid;
 
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+enum GridSystem {
+    Translation,
+}
+
+/// Draw layer of an entity that is placed on the grid. Together with the row
+/// of its `Position` it determines the z of the entity: everything in a row
+/// further south is drawn in front of the rows behind it, so tall sprites are
+/// occluded by the blocks in front of them. Within a row the layers are drawn
+/// in the order below. The floor is flat and always drawn behind all rows.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Floor,
+    Block,
+    Item,
+    Bomb,
+    Flame,
+    Player,
+}
+
 #[derive(Component)]
 pub struct DebugGrid;
 
 pub const WIDTH: i32 = 15;
 pub const HEIGHT: i32 = 11;
 
+const FLOOR_Z: f32 = 20.0;
+/// z of the layers in row 0, every next row is one higher.
+const ROW_Z: f32 = 21.0;
+
 const GRID_OFFSET: Vec2 = const_vec2!([20.0, -64.0]);
 const CELL_DIMENSION: Vec2 = const_vec2!([40.0, -36.0]);
 
@@ -78,6 +104,23 @@ fn position_translation(mut query: Query<(&Position, &Offset, &mut Transform), W
     }
 }
 
+impl Layer {
+    pub fn z(&self, row: i32) -> f32 {
+        match self {
+            Layer::Floor => FLOOR_Z,
+            // Layers are spaced 0.1 apart, which leaves room for the 6 layers
+            // within a row.
+            layer => ROW_Z + row as f32 + (*layer as u8) as f32 * 0.1,
+        }
+    }
+}
+
+fn y_sort(mut query: Query<(&Position, &Layer, &mut Transform)>) {
+    for (pos, layer, mut transform) in query.iter_mut() {
+        transform.translation.z = layer.z(pos.y);
+    }
+}
+
 pub struct PositionIterator {
     pos: Option<Position>,
     dir: Direction,
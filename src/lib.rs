@@ -1,11 +1,15 @@
 #![feature(drain_filter, hash_drain_filter)]
 
 pub mod animation;
+pub mod asset_io;
 pub mod asset_loaders;
 pub mod game;
+pub mod game_mode;
 pub mod loading;
 pub mod state;
 pub mod window;
 pub mod player;
 pub mod bomb;
 pub mod grid;
+pub mod powerup;
+pub mod score;
@@ -2,7 +2,10 @@ use crate::animation::*;
 use crate::asset_loaders::*;
 use crate::state::*;
 
-use bevy::prelude::*;
+use bevy::{
+    asset::{Asset, LoadState},
+    prelude::*,
+};
 use std::collections::HashMap;
 use std::path::Path;
 
@@ -17,256 +20,59 @@ impl Plugin for LoadingPlugin {
             .add_system_set(SystemSet::on_enter(AppState::Loading).with_system(setup))
             .add_system_set(
                 SystemSet::on_update(AppState::Loading)
+                    .with_system(load_lists)
                     .with_system(load_animations)
                     .with_system(load_sounds)
                     .with_system(load_schemes)
+                    .with_system(recolor_animations)
                     .with_system(loading_progress),
             )
             .add_system_set(SystemSet::on_exit(AppState::Loading).with_system(cleanup));
     }
 }
 
-/// All animations listed in DATA/ANI/MASTER.ALI
-const ANIMATION_LIST: &'static [&str] = &[
-    // "ALIENS1.ANI",
-    "BOMBS.ANI",
-    // "BWALK1.ANI",
-    // "BWALK2.ANI",
-    // "BWALK3.ANI",
-    // "BWALK4.ANI",
-    // "CONVEYOR.ANI",
-    // "CORNER0.ANI",
-    // "CORNER1.ANI",
-    // "CORNER2.ANI",
-    // "CORNER3.ANI",
-    // "CORNER4.ANI",
-    // "CORNER5.ANI",
-    // "CORNER6.ANI",
-    // "CORNER7.ANI",
-    // "DUDS.ANI",
-    // "EDIT.ANI",
-    // "EXTRAS.ANI",
-    // "HURRY.ANI",
-    // "KFACE.ANI",
-    // "KFONT.ANI",
-    // "KICK.ANI",
-    "MFLAME.ANI",
-    // "MISC.ANI",
-    // "POWERS.ANI",
-    // "PUNBOMB1.ANI",
-    // "PUNBOMB2.ANI",
-    // "PUNBOMB3.ANI",
-    // "PUNBOMB4.ANI",
-    // "PUP1.ANI",
-    // "PUP2.ANI",
-    // "PUP3.ANI",
-    // "PUP4.ANI",
-    "SHADOW.ANI",
-    "STAND.ANI",
-    "TILES0.ANI",
-    // "TILES1.ANI",
-    // "TILES10.ANI",
-    // "TILES2.ANI",
-    // "TILES3.ANI",
-    // "TILES4.ANI",
-    // "TILES5.ANI",
-    // "TILES6.ANI",
-    // "TILES7.ANI",
-    // "TILES8.ANI",
-    // "TILES9.ANI",
-    // "TRIGANIM.ANI",
-    "WALK.ANI",
-    "XBRICK0.ANI",
-    // "XBRICK1.ANI",
-    // "XBRICK10.ANI",
-    // "XBRICK2.ANI",
-    // "XBRICK3.ANI",
-    // "XBRICK4.ANI",
-    // "XBRICK5.ANI",
-    // "XBRICK6.ANI",
-    // "XBRICK7.ANI",
-    // "XBRICK8.ANI",
-    // "XBRICK9.ANI",
-    // "XPLODE1.ANI",
-    // "XPLODE10.ANI",
-    // "XPLODE11.ANI",
-    // "XPLODE12.ANI",
-    // "XPLODE13.ANI",
-    // "XPLODE14.ANI",
-    // "XPLODE15.ANI",
-    // "XPLODE16.ANI",
-    // "XPLODE17.ANI",
-    // "XPLODE2.ANI",
-    // "XPLODE3.ANI",
-    // "XPLODE4.ANI",
-    // "XPLODE5.ANI",
-    // "XPLODE6.ANI",
-    // "XPLODE7.ANI",
-    // "XPLODE8.ANI",
-    // "XPLODE9.ANI",
-];
-
-/// All sounds listed in DATA/RES/SOUNDLST.RES
-const SOUND_LIST: &'static [&str] = &[
-    "1006.RSS",
-    "1017.RSS",
-    "1028.RSS",
-    "1036.RSS",
-    "1041.RSS",
-    "1045.RSS",
-    "1049.RSS",
-    "1055.RSS",
-    "1059.RSS",
-    "1062.RSS",
-    "1074.RSS",
-    "2MUCPAIN.RSS",
-    "ALLRITE.RSS",
-    "BACKUP.RSS",
-    "BACKUP1.RSS",
-    "BMBSTOP1.RSS",
-    "BMBSTOP2.RSS",
-    "BMBTHRW1.RSS",
-    "BMBTHRW3.RSS",
-    "BMBTHRW4.RSS",
-    "BMBTHRW5.RSS",
-    "BMDROP1.RSS",
-    "BMDROP2.RSS",
-    "BMDROP3.RSS",
-    "BOMBBOUN.RSS",
-    "BOMBHIT1.RSS",
-    "BOMBHIT2.RSS",
-    "BOMBHIT3.RSS",
-    "BOMBHIT4.RSS",
-    "BOMBSTOP.RSS",
-    "BOMB_01.RSS",
-    "BOMB_02.RSS",
-    "BOMB_04.RSS",
-    "BOMB_04B.RSS",
-    "BOMB_05.RSS",
-    "BOMB_06.RSS",
-    "BOMB_07.RSS",
-    "BOMB_07B.RSS",
-    "BOMB_09.RSS",
-    "BOMB_11.RSS",
-    "BOMB_12.RSS",
-    "BOMB_12B.RSS",
-    "BOMB_17.RSS",
-    "BOMB_19.RSS",
-    "BOMB_24.RSS",
-    "BRINGON.RSS",
-    "BURNEDUP.RSS",
-    "CALLDAD.RSS",
-    "CLEAR.RSS",
-    "CLIKPLAT.RSS",
-    "COMEGET.RSS",
-    "COOLPOP.RSS",
-    "CRIBROWN.RSS",
-    "CUL8R.RSS",
-    "DABOMB.RSS",
-    "DESTU1.RSS",
-    "DESTU2.RSS",
-    "DIE1.RSS",
-    "dis2a.rss",
-    "DRABOM.RSS",
-    "DRAW.RSS",
-    "EATDUST.RSS",
-    "ELAUGH1.RSS",
-    "ENRT1.RSS",
-    "ENRT2.RSS",
-    "EWTAHH.RSS",
-    "EXPL6.RSS",
-    "EXPLO1.RSS",
-    "EXPLODE2.RSS",
-    "EXPLODE3.RSS",
-    "EXPLODE4.RSS",
-    "FEELPOWR.RSS",
-    "FILLCRAK.RSS",
-    "FIREINH.RSS",
-    "GEN8A.RSS",
-    "GET1.RSS",
-    "GET2.RSS",
-    "GOTAHURT.RSS",
-    "GOTCHA.RSS",
-    "GRAB1.RSS",
-    "GRAB2.RSS",
-    "GUMP1.RSS",
-    "HITSTRID.RSS",
-    "HURRY.RSS",
-    "HURYTUF.RSS",
-    "INZONE.RSS",
-    "KBOMB1.RSS",
-    "KBOMB2.RSS",
-    "KICKER10.RSS",
-    "KICKER3.RSS",
-    "LATER.RSS",
-    "LETTER1.RSS",
-    "LETTER2.RSS",
-    "LITEMUP.RSS",
-    "LOOKOUT.RSS",
-    "LOSE.RSS",
-    "LOVNIT1.RSS",
-    "LUVYAP.RSS",
-    "MENU.RSS",
-    "MENUEXIT.RSS",
-    "MOLASES.RSS",
-    "NETWORK.RSS",
-    "OHHHH.RSS",
-    "OHNO1.RSS",
-    "OHYEAH.RSS",
-    "PACKIN.RSS",
-    "PGAD12B.RSS",
-    "PLAYPROP.RSS",
-    "POOPS1.RSS",
-    "POOPS2.RSS",
-    "POOPS3.RSS",
-    "POOPS4.RSS",
-    "POOPS5.RSS",
-    "PROUD.RSS",
-    "QUITGAME.RSS",
-    "ROASTED.RSS",
-    "RUNAWAY1.RSS",
-    "RUNAWAY2.RSS",
-    "SCHWING.RSS",
-    "SCREAM1.RSS",
-    "SERENDIP.RSS",
-    "SHAKEBOO.RSS",
-    "SMELSMOK.RSS",
-    "SQRDROP2.RSS",
-    "SQRDROP4.RSS",
-    "SQRDROP5.RSS",
-    "SQRDROP6.RSS",
-    "SQRDROP7.RSS",
-    "SQRDROP8.RSS",
-    "STUPIDIO.RSS",
-    "SUCKITDN.RSS",
-    "TASTPAI2.RSS",
-    "TASTPAIN.RSS",
-    "THEMAN.RSS",
-    "TOEASY.RSS",
-    "TRAMPO.RSS",
-    "WANTMNKY.RSS",
-    "WARP1.RSS",
-    "WHATRUSH.RSS",
-    "WHODAD.RSS",
-    "WIN.RSS",
-    "WLKTAL1.RSS",
-    "WLKTAL2.RSS",
-    "WOOHOO1.RSS",
-    "YEEHAW.RSS",
-    "YOUBLOW.RSS",
-    "YOUWIN1.RSS",
-    "ZEN1.RSS",
-    "ZEN2.RSS",
-    "disease1.rss",
-    "disease2.rss",
-    "disease3.rss",
-];
+/// A list file, and where the files with the given extension in it are
+/// loaded from. Files with other extensions are ignored.
+pub struct AssetList {
+    pub list: &'static str,
+    pub dir: &'static str,
+    pub extension: &'static str,
+}
+
+impl AssetList {
+    fn path(&self, filename: &str) -> String {
+        format!("{}/{}", self.dir, filename)
+    }
+}
+
+/// Lists of the animations and sounds to load.
+pub const ANIMATION_LIST: AssetList = AssetList {
+    list: "data/ANI/MASTER.ALI",
+    dir: "data/ANI",
+    extension: "ani",
+};
+pub const SOUND_LIST: AssetList = AssetList {
+    list: "data/RES/SOUNDLST.RES",
+    dir: "data/SOUND",
+    extension: "rss",
+};
+
+/// Animations that are drawn in the color of a player. They are recolored with
+/// the remap table of every player color.
+const RECOLORED_ANIMATIONS: &'static [&str] = &["BOMBS.ANI", "MFLAME.ANI", "STAND.ANI", "WALK.ANI"];
 
 #[derive(Default)]
 struct AssetsLoading {
+    /// The lists are `None` once the files in them are being loaded.
+    animation_list: Option<Handle<FileList>>,
+    sound_list: Option<Handle<FileList>>,
     animations: Vec<Handle<AnimationBundle>>,
-    sounds: HashMap<String, Handle<AudioSource>>,
+    sounds: HashMap<String, Handle<RawSound>>,
     schemes: Vec<Handle<Scheme>>,
+    color_palette: Handle<ColorPalette>,
+    color_remap_tables: Vec<Handle<ColorRemapTable>>,
+    recolor: Vec<Handle<AnimationBundle>>,
+    recolored: bool,
     initial_count: usize,
 }
 
@@ -316,31 +122,18 @@ fn setup(
 
     commands.insert_resource(LoadingScreen { entity });
 
-    let color_palette: Handle<ColorPalette> = asset_server.load("data/COLOR.PAL");
-    println!("{:?}", color_palette);
-    let mut color_remap_tables: Vec<Handle<ColorRemapTable>> = vec![];
-    for i in 0..10 {
+    assets_loading.color_palette = asset_server.load("data/COLOR.PAL");
+    for i in 0..PLAYER_COLORS {
         let path = format!("data/{}.RMP", i);
-        color_remap_tables.push(asset_server.load(path.as_str()));
-    }
-
-    // TODO: pass color_palette and color_remap_tables to animations.
-    for filename in ANIMATION_LIST {
-        let path = "data/ANI/".to_owned() + filename;
         assets_loading
-            .animations
+            .color_remap_tables
             .push(asset_server.load(path.as_str()));
-        assets_loading.initial_count += 1;
     }
 
-    for filename in SOUND_LIST {
-        let path = "data/SOUND/".to_owned() + filename;
-        let filename = (*filename).to_owned();
-        assets_loading
-            .sounds
-            .insert(filename, asset_server.load(path.as_str()));
-        assets_loading.initial_count += 1;
-    }
+    // The lists and recoloring count as one asset each.
+    assets_loading.animation_list = Some(asset_server.load(ANIMATION_LIST.list));
+    assets_loading.sound_list = Some(asset_server.load(SOUND_LIST.list));
+    assets_loading.initial_count += 3;
 
     assets_loading.schemes = asset_server
         .load_folder("data/SCHEMES")
@@ -352,17 +145,76 @@ fn setup(
     assets_loading.initial_count += assets_loading.schemes.len();
 }
 
+/// Once a list is loaded, start loading the files in it.
+fn load_lists(
+    mut assets_loading: ResMut<AssetsLoading>,
+    asset_server: Res<AssetServer>,
+    list_assets: Res<Assets<FileList>>,
+) {
+    let assets_loading = &mut *assets_loading;
+
+    if let Some(list) = loaded_list(
+        &mut assets_loading.animation_list,
+        &asset_server,
+        &list_assets,
+    ) {
+        for filename in list.with_extension(ANIMATION_LIST.extension) {
+            let handle = asset_server.load(ANIMATION_LIST.path(filename).as_str());
+            let recolor = RECOLORED_ANIMATIONS
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(filename));
+            if recolor {
+                assets_loading.recolor.push(handle.clone());
+            }
+            assets_loading.animations.push(handle);
+            assets_loading.initial_count += 1;
+        }
+    }
+
+    if let Some(list) = loaded_list(&mut assets_loading.sound_list, &asset_server, &list_assets) {
+        for filename in list.with_extension(SOUND_LIST.extension) {
+            let path = SOUND_LIST.path(filename);
+            assets_loading
+                .sounds
+                .insert(filename.to_owned(), asset_server.load(path.as_str()));
+            assets_loading.initial_count += 1;
+        }
+    }
+}
+
+/// Takes the handle of a list once it is loaded. A list that fails to load
+/// is dropped, none of its files are loaded.
+fn loaded_list<'a>(
+    handle: &mut Option<Handle<FileList>>,
+    asset_server: &AssetServer,
+    list_assets: &'a Assets<FileList>,
+) -> Option<&'a FileList> {
+    if has_failed(asset_server, handle.as_ref()?) {
+        let handle = handle.take()?;
+        error!("failed to load {:?}", asset_server.get_handle_path(&handle));
+        return None;
+    }
+
+    let list = list_assets.get(handle.as_ref()?)?;
+    *handle = None;
+    Some(list)
+}
+
 fn load_animations(
     mut assets_loading: ResMut<AssetsLoading>,
     mut named_assets: ResMut<NamedAssets>,
+    asset_server: Res<AssetServer>,
     animation_bundle_assets: Res<Assets<AnimationBundle>>,
 ) {
-    let done = assets_loading
-        .animations
-        .drain_filter(|handle| animation_bundle_assets.contains(handle.clone()));
+    let done = assets_loading.animations.drain_filter(|handle| {
+        animation_bundle_assets.contains(handle.clone()) || has_failed(&asset_server, handle)
+    });
 
     for handle in done {
-        let bundle = animation_bundle_assets.get(handle).unwrap();
+        let bundle = match animation_bundle_assets.get(handle) {
+            Some(bundle) => bundle,
+            None => continue,
+        };
         for (name, animation_handle) in bundle.animations.iter() {
             named_assets
                 .animations
@@ -374,13 +226,17 @@ fn load_animations(
 fn load_sounds(
     mut assets_loading: ResMut<AssetsLoading>,
     mut named_assets: ResMut<NamedAssets>,
-    sound_assets: Res<Assets<AudioSource>>,
+    asset_server: Res<AssetServer>,
+    sound_assets: Res<Assets<RawSound>>,
 ) {
-    let done = assets_loading
-        .sounds
-        .drain_filter(|_filename, handle| sound_assets.contains(handle.clone()));
+    let done = assets_loading.sounds.drain_filter(|_filename, handle| {
+        sound_assets.contains(handle.clone()) || has_failed(&asset_server, handle)
+    });
 
     for (filename, handle) in done {
+        if !sound_assets.contains(handle.clone()) {
+            continue;
+        }
         let name = Path::new(&filename)
             .file_stem()
             .unwrap()
@@ -391,6 +247,11 @@ fn load_sounds(
     }
 }
 
+/// Files that fail to load are skipped, the asset server logs why.
+fn has_failed<T: Asset>(asset_server: &AssetServer, handle: &Handle<T>) -> bool {
+    asset_server.get_load_state(handle) == LoadState::Failed
+}
+
 fn load_schemes(
     mut assets_loading: ResMut<AssetsLoading>,
     mut named_assets: ResMut<NamedAssets>,
@@ -406,14 +267,110 @@ fn load_schemes(
     }
 }
 
+/// Once the palette, the remap tables and the animations are loaded, create a
+/// recolored sprite sheet for every player color.
+#[allow(clippy::too_many_arguments)]
+fn recolor_animations(
+    mut assets_loading: ResMut<AssetsLoading>,
+    mut named_assets: ResMut<NamedAssets>,
+    asset_server: Res<AssetServer>,
+    animation_bundle_assets: Res<Assets<AnimationBundle>>,
+    palette_assets: Res<Assets<ColorPalette>>,
+    table_assets: Res<Assets<ColorRemapTable>>,
+    mut animation_assets: ResMut<Assets<Animation>>,
+    mut atlas_assets: ResMut<Assets<TextureAtlas>>,
+    mut image_assets: ResMut<Assets<Image>>,
+) {
+    // Wait until the animations to recolor are known.
+    if assets_loading.recolored || assets_loading.animation_list.is_some() {
+        return;
+    }
+
+    // Without the palette or a remap table the animations keep their original
+    // colors, see `NamedAssets::colored_animation`.
+    let failed = has_failed(&asset_server, &assets_loading.color_palette)
+        || assets_loading
+            .color_remap_tables
+            .iter()
+            .any(|handle| has_failed(&asset_server, handle));
+    if failed {
+        error!("Not recoloring animations, COLOR.PAL or a remap table failed to load");
+        assets_loading.recolored = true;
+        return;
+    }
+
+    let palette = match palette_assets.get(&assets_loading.color_palette) {
+        Some(palette) => palette,
+        None => return,
+    };
+
+    let tables: Option<Vec<&ColorRemapTable>> = assets_loading
+        .color_remap_tables
+        .iter()
+        .map(|handle| table_assets.get(handle))
+        .collect();
+    let bundles: Option<Vec<&AnimationBundle>> = assets_loading
+        .recolor
+        .iter()
+        .filter(|handle| !has_failed(&asset_server, handle))
+        .map(|handle| animation_bundle_assets.get(handle))
+        .collect();
+    let (tables, bundles) = match (tables, bundles) {
+        (Some(tables), Some(bundles)) => (tables, bundles),
+        _ => return,
+    };
+
+    for bundle in bundles {
+        // The animations of a bundle share their atlas, recolor it only once.
+        let mut colored_atlases: HashMap<Handle<TextureAtlas>, Vec<Handle<TextureAtlas>>> =
+            HashMap::new();
+
+        for (name, handle) in bundle.animations.iter() {
+            let animation = animation_assets.get(handle).unwrap().clone();
+
+            let atlases = colored_atlases
+                .entry(animation.atlas.texture.clone())
+                .or_insert_with(|| {
+                    let atlas = atlas_assets.get(&animation.atlas.texture).unwrap().clone();
+                    let image = image_assets.get(&atlas.texture).unwrap().clone();
+
+                    tables
+                        .iter()
+                        .map(|table| {
+                            let mut atlas = atlas.clone();
+                            atlas.texture = image_assets.add(table.remap_image(palette, &image));
+                            atlas_assets.add(atlas)
+                        })
+                        .collect()
+                });
+
+            let handles = atlases
+                .iter()
+                .map(|atlas| {
+                    let mut animation = animation.clone();
+                    animation.atlas.texture = atlas.clone();
+                    animation_assets.add(animation)
+                })
+                .collect();
+
+            named_assets.colored_animations.insert(name.clone(), handles);
+        }
+    }
+
+    assets_loading.recolored = true;
+}
+
 fn loading_progress(
     mut state: ResMut<State<AppState>>,
     assets_loading: ResMut<AssetsLoading>,
     mut query: Query<&mut Text, With<LoadingText>>,
 ) {
-    let remaining_count = assets_loading.animations.len()
+    let remaining_count = assets_loading.animation_list.is_some() as usize
+        + assets_loading.sound_list.is_some() as usize
+        + assets_loading.animations.len()
         + assets_loading.sounds.len()
-        + assets_loading.schemes.len();
+        + assets_loading.schemes.len()
+        + !assets_loading.recolored as usize;
     let assets_loaded = assets_loading.initial_count - remaining_count;
 
     for mut text in query.iter_mut() {
@@ -1,5 +1,9 @@
-use atomic_bomberman::{game::GamePlugin, loading::LoadingPlugin, state::*, window::WindowPlugin};
+use atomic_bomberman::{
+    asset_io::AssetIoPlugin, game::GamePlugin, loading::LoadingPlugin, state::*,
+    window::WindowPlugin,
+};
 use bevy::{
+    asset::AssetPlugin,
     diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
     prelude::*,
 };
@@ -7,13 +11,8 @@ use bevy::{
 // TODO: use https://bevy-cheatbook.github.io/features/audio.html
 // TODO: https://bevy-cheatbook.github.io/features/fixed-timestep.html
 
-// TODO: Generate animations based on color.pal (other asset)
-// Could listen to AssetEvent to wait for palette resource to be loaded.
-// How to pass data to AssetLoaders?
-//
-// https://github.com/bevyengine/bevy/pull/1665
-// https://github.com/bevyengine/bevy/pull/1393
-// https://github.com/bevyengine/bevy/discussions/1307
+// Player colored animations are generated from COLOR.PAL and the RMP tables
+// once all of them are loaded, see `loading::recolor_animations`.
 
 // COLOR.PAL: (= palette)
 // 256 RGB colors (768 bytes).
@@ -44,7 +43,9 @@ fn main() {
             level: bevy::log::Level::INFO,
             ..Default::default()
         })
-        .add_plugins(DefaultPlugins)
+        .add_plugins_with(DefaultPlugins, |group| {
+            group.add_before::<AssetPlugin, _>(AssetIoPlugin)
+        })
         .add_plugin(WindowPlugin)
         .add_plugin(FrameTimeDiagnosticsPlugin)
         .add_plugin(LoadingPlugin)
@@ -1,8 +1,9 @@
 use crate::{
     animation::*,
     asset_loaders::*,
-    bomb::PlaceBombEvent,
-    grid::{Direction, Position},
+    bomb::{Flame, FlamePart, PlaceBombEvent},
+    grid::{Direction, Layer, Position},
+    powerup::CollectedPowerups,
     state::*,
 };
 use bevy::{input::keyboard::KeyboardInput, prelude::*};
@@ -14,11 +15,14 @@ pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
+        app.add_event::<PlayerDiedEvent>().add_system_set(
             SystemSet::on_update(AppState::Game)
                 .with_system(keyboard_handling)
                 .with_system(player_movement)
-                .with_system(change_sprite),
+                .with_system(animate_player)
+                .with_system(player_death)
+                .with_system(despawn_dead_players)
+                .with_system(blink_invulnerable),
         );
     }
 }
@@ -26,6 +30,18 @@ impl Plugin for PlayerPlugin {
 #[derive(Component)]
 pub struct Player;
 
+/// Identifies a player for the whole match, unlike its entity which is
+/// replaced every round. Used to credit bombs, flames and kills.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PlayerId(pub u8);
+
+impl PlayerId {
+    /// Index of the player color, see `NamedAssets::colored_animation`.
+    pub fn color(&self) -> usize {
+        self.0 as usize % PLAYER_COLORS
+    }
+}
+
 #[derive(Component)]
 pub struct PlayerSpeed{
     pub timer: Timer
@@ -40,22 +56,196 @@ pub struct PlayerDirection {
 #[derive(Component, Default)]
 pub struct KeyboardMovement(Vec<Direction>);
 
-impl PlayerDirection {
-    pub fn animation(&self) -> String {
-        let dir = match self.direction {
-            Direction::North => "north",
-            Direction::East => "east",
-            Direction::South => "south",
-            Direction::West => "west",
-        };
-        let ani = match self.walking {
-            true => "walk",
-            false => "stand",
-        };
-        format!("{} {}", ani, dir)
+/// Flames can't hurt the player while this is present. The player sprites
+/// blink until it wears off, e.g. right after a respawn.
+#[derive(Component)]
+pub struct Invulnerable {
+    pub timer: Timer,
+    pub blink: Timer,
+}
+
+impl Invulnerable {
+    pub fn from_seconds(duration: f32) -> Self {
+        Invulnerable {
+            timer: Timer::from_seconds(duration, false),
+            blink: Timer::from_seconds(0.1, true),
+        }
+    }
+}
+
+/// Sent when a player is caught by a flame. The player entity loses its
+/// `Player` component and is `Dying` until the die animation finished, so
+/// everything that is needed afterwards is copied into the event.
+pub struct PlayerDiedEvent {
+    pub player: Entity,
+    pub id: PlayerId,
+    /// Owner of the flame that killed the player.
+    pub killer: PlayerId,
+    pub position: Position,
+    pub powerups: Vec<Powerup>,
+}
+
+impl PlayerDiedEvent {
+    pub fn is_suicide(&self) -> bool {
+        self.killer == self.id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerAction {
+    Stand,
+    Walk,
+    Die,
+    Kick,
+    Punch,
+    Pickup,
+    Carry,
+}
+
+impl PlayerAction {
+    fn name(&self) -> &'static str {
+        match self {
+            PlayerAction::Stand => "stand",
+            PlayerAction::Walk => "walk",
+            PlayerAction::Die => "die",
+            PlayerAction::Kick => "kick",
+            PlayerAction::Punch => "punch",
+            PlayerAction::Pickup => "pickup",
+            PlayerAction::Carry => "carry",
+        }
+    }
+
+    /// Actions that are played once, instead of for as long as the player
+    /// stands or walks.
+    pub fn is_one_shot(&self) -> bool {
+        matches!(
+            self,
+            PlayerAction::Die | PlayerAction::Kick | PlayerAction::Punch | PlayerAction::Pickup
+        )
+    }
+}
+
+/// Animation controller of a player. It switches the animation of the player
+/// sprite in place whenever the action, direction or disease changes. A
+/// player is diseased while holding a disease powerup.
+#[derive(Component)]
+pub struct PlayerAnimation {
+    pub action: PlayerAction,
+    /// Whether the player holds a bomb, it then carries instead of standing
+    /// or walking.
+    pub carrying: bool,
+    /// Child entity with the player sprite.
+    sprite: Entity,
+    /// Action, direction and disease the sprite currently shows.
+    shown: Option<(PlayerAction, Direction, bool)>,
+}
+
+impl PlayerAnimation {
+    fn new(sprite: Entity) -> Self {
+        PlayerAnimation {
+            action: PlayerAction::Stand,
+            carrying: false,
+            sprite,
+            shown: None,
+        }
+    }
+
+    /// Play a one-shot action, e.g. a kick. Afterwards the player goes back
+    /// to standing or walking, except after dying.
+    pub fn play(&mut self, action: PlayerAction) {
+        self.action = action;
     }
 }
 
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::North => "north",
+        Direction::East => "east",
+        Direction::South => "south",
+        Direction::West => "west",
+    }
+}
+
+/// Look up the animation for an action. Not every action has a disease
+/// variant or one per direction, those fall back to the plain animation.
+fn player_animation(
+    named_assets: &NamedAssets,
+    id: PlayerId,
+    action: PlayerAction,
+    direction: Direction,
+    diseased: bool,
+) -> Option<&Handle<Animation>> {
+    let dir = direction_name(direction);
+    let candidates = [
+        diseased.then(|| format!("{} disease {}", action.name(), dir)),
+        Some(format!("{} {}", action.name(), dir)),
+        Some(action.name().to_owned()),
+        Some(format!("stand {}", dir)),
+    ];
+
+    candidates
+        .iter()
+        .flatten()
+        .find_map(|name| named_assets.colored_animation(name, id.color()))
+}
+
+/// Spawn a player standing at `pos`, with the shadow and sprite as children.
+pub fn spawn_player(
+    commands: &mut Commands,
+    named_assets: &NamedAssets,
+    animation_assets: &Assets<Animation>,
+    id: PlayerId,
+    pos: Position,
+) -> Entity {
+    let player_direction = PlayerDirection {
+        direction: Direction::North,
+        walking: false,
+    };
+
+    let animation = player_animation(
+        named_assets,
+        id,
+        PlayerAction::Stand,
+        player_direction.direction,
+        false,
+    )
+    .unwrap();
+
+    let shadow = named_assets.animations.get("shadow").unwrap();
+    let shadow = commands
+        .spawn_bundle(AnimatedSpriteBundle::new(
+            shadow.clone(),
+            animation_assets,
+            Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
+        ))
+        .id();
+
+    let sprite = commands
+        .spawn_bundle(AnimatedSpriteBundle::new(
+            animation.clone(),
+            animation_assets,
+            Transform::default(),
+        ))
+        .id();
+
+    commands
+        .spawn_bundle((
+            Transform::from_translation(Vec3::from((pos.into(), 30.0))),
+            GlobalTransform::default(),
+        ))
+        .insert(Player)
+        .insert(id)
+        .insert(player_direction)
+        .insert(KeyboardMovement::default())
+        .insert(pos)
+        .insert(Layer::Player)
+        .insert(PlayerSpeed{ timer: Timer::from_seconds(1. / 60., true)})
+        .insert(CollectedPowerups::default())
+        .insert(PlayerAnimation::new(sprite))
+        .push_children(&[shadow, sprite])
+        .id()
+}
+
 fn key_to_direction(key_code: &KeyCode) -> Option<Direction> {
     match key_code {
         KeyCode::A => Some(Direction::West),
@@ -68,12 +258,12 @@ fn key_to_direction(key_code: &KeyCode) -> Option<Direction> {
 
 fn keyboard_handling(
     mut keyboard_events: EventReader<KeyboardInput>,
-    mut query: Query<(&mut PlayerDirection, &mut KeyboardMovement, &Position)>,
+    mut query: Query<(&mut PlayerDirection, &mut KeyboardMovement, &Position, &PlayerId)>,
     mut place_bomb_events: EventWriter<PlaceBombEvent>,
 ) {
     // TODO: need to remember which keys are pressed.
     // maybe the naive input handling is enough?
-    for (mut player_direction, mut keyboard_movement, pos) in query.iter_mut() {
+    for (mut player_direction, mut keyboard_movement, pos, id) in query.iter_mut() {
         for event in keyboard_events.iter() {
             if let Some(key_code) = event.key_code {
                 if let Some(direction) = key_to_direction(&key_code) {
@@ -94,7 +284,7 @@ fn keyboard_handling(
                         player_direction.walking = false;
                     }
                 } else if event.state.is_pressed() && key_code == KeyCode::Space {
-                    place_bomb_events.send(PlaceBombEvent(*pos));
+                    place_bomb_events.send(PlaceBombEvent(*pos, *id));
                 }
             }
         }
@@ -140,33 +330,204 @@ fn player_movement(
     }
 }
 
-// NOTE: systems with Changed<> must be added AFTER the system that triggers the change.
-fn change_sprite(
-    mut commands: Commands,
+fn animate_player(
     named_assets: Res<NamedAssets>,
     animation_assets: Res<Assets<Animation>>,
-    mut query: Query<(Entity, &mut Children, &PlayerDirection), Changed<PlayerDirection>>,
+    mut finished_events: EventReader<AnimationFinished>,
+    mut players: Query<(
+        &PlayerId,
+        &PlayerDirection,
+        &CollectedPowerups,
+        &mut PlayerAnimation,
+    )>,
+    mut sprites: Query<&mut AnimatedSprite>,
 ) {
-    for (entity, children, player_direction) in query.iter_mut() {
-        if let Some(child) = children.last().copied() {
-            commands.entity(child).despawn_recursive();
+    let finished: HashSet<Entity> = finished_events.iter().map(|event| event.entity).collect();
+
+    for (id, player_direction, collected, mut animation) in players.iter_mut() {
+        if animation.action.is_one_shot()
+            && animation.action != PlayerAction::Die
+            && finished.contains(&animation.sprite)
+        {
+            animation.action = PlayerAction::Stand;
         }
 
-        let animation = named_assets
-            .animations
-            .get(&player_direction.animation())
-            .unwrap();
-
-        let child = commands
-            .spawn_bundle(AnimatedSpriteBundle::new(
-                animation.clone(),
-                &animation_assets,
-                // 55 = 110 (height) / 2, 18 = 36 (tile height) / 2
-                // when position is translated into vec2, it points to the center, hence the correction for -18.
-                Transform::from_translation(Vec3::new(0.0, 55.0 - 18.0, 0.0)),
-            ))
-            .id();
-
-        commands.entity(entity).push_children(&[child]);
+        if !animation.action.is_one_shot() {
+            animation.action = if animation.carrying {
+                PlayerAction::Carry
+            } else if player_direction.walking {
+                PlayerAction::Walk
+            } else {
+                PlayerAction::Stand
+            };
+        }
+
+        let state = (
+            animation.action,
+            player_direction.direction,
+            collected.is_diseased(),
+        );
+        if animation.shown == Some(state) {
+            continue;
+        }
+
+        let (action, direction, diseased) = state;
+        let handle = match player_animation(&named_assets, *id, action, direction, diseased) {
+            Some(handle) => handle,
+            None => continue,
+        };
+
+        if let Ok(mut sprite) = sprites.get_mut(animation.sprite) {
+            // Turning around or catching a disease continues the current
+            // action, only a new action starts at its first frame.
+            let keep_frame = matches!(animation.shown, Some((shown, _, _)) if shown == action);
+            let mode = match action.is_one_shot() {
+                true => PlaybackMode::Once,
+                false => PlaybackMode::Loop,
+            };
+            let asset = animation_assets.get(handle).unwrap();
+            sprite.set_animation(handle.clone(), asset, mode, keep_frame);
+        }
+
+        animation.shown = Some(state);
+    }
+}
+
+/// Players that can be hurt by flames.
+type VulnerablePlayers<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static PlayerId,
+        &'static Position,
+        &'static mut CollectedPowerups,
+        &'static mut PlayerDirection,
+        &'static mut PlayerAnimation,
+    ),
+    (With<Player>, Without<Invulnerable>),
+>;
+
+fn player_death(
+    mut commands: Commands,
+    mut died_events: EventWriter<PlayerDiedEvent>,
+    mut players: VulnerablePlayers,
+    flame_parts: Query<(&Position, &Parent), With<FlamePart>>,
+    flames: Query<&Flame>,
+) {
+    for (entity, id, pos, mut collected, mut direction, mut animation) in players.iter_mut() {
+        let flame = flame_parts
+            .iter()
+            .find(|(p, _parent)| p == &pos)
+            .and_then(|(_p, parent)| flames.get(parent.0).ok());
+
+        if let Some(flame) = flame {
+            debug!("player {:?} killed by {:?} at {:?}", id, flame.owner, pos);
+            died_events.send(PlayerDiedEvent {
+                player: entity,
+                id: *id,
+                killer: flame.owner,
+                position: *pos,
+                powerups: collected.0.drain(..).collect(),
+            });
+
+            direction.walking = false;
+            animation.play(PlayerAction::Die);
+            commands
+                .entity(entity)
+                .remove::<Player>()
+                .remove::<KeyboardMovement>()
+                .remove::<PlayerSpeed>()
+                .insert(Dying);
+        }
+    }
+}
+
+/// The body of a player that died, despawned once the die animation finished.
+#[derive(Component)]
+pub struct Dying;
+
+fn despawn_dead_players(
+    mut commands: Commands,
+    mut finished_events: EventReader<AnimationFinished>,
+    players: Query<(Entity, &PlayerAnimation), With<Dying>>,
+) {
+    let finished: HashSet<Entity> = finished_events.iter().map(|event| event.entity).collect();
+
+    for (entity, animation) in players.iter() {
+        if finished.contains(&animation.sprite) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn blink_invulnerable(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut players: Query<(Entity, &mut Invulnerable, &Children)>,
+    mut sprites: Query<&mut Visibility>,
+) {
+    for (entity, mut invulnerable, children) in players.iter_mut() {
+        invulnerable.timer.tick(time.delta());
+        invulnerable.blink.tick(time.delta());
+
+        let done = invulnerable.timer.finished();
+        if done {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+
+        if done || invulnerable.blink.just_finished() {
+            for child in children.iter() {
+                if let Ok(mut visibility) = sprites.get_mut(*child) {
+                    visibility.is_visible = done || !visibility.is_visible;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::HandleId;
+
+    #[test]
+    fn every_action_has_an_animation() {
+        let actions = [
+            PlayerAction::Stand,
+            PlayerAction::Walk,
+            PlayerAction::Die,
+            PlayerAction::Kick,
+            PlayerAction::Punch,
+            PlayerAction::Pickup,
+            PlayerAction::Carry,
+        ];
+
+        let mut named_assets = NamedAssets::default();
+        for action in actions {
+            for name in [
+                format!("{} north", action.name()),
+                format!("{} disease north", action.name()),
+            ] {
+                let handle = Handle::weak(HandleId::random::<Animation>());
+                named_assets.animations.insert(name, handle);
+            }
+        }
+
+        for action in actions {
+            for (diseased, name) in [
+                (false, format!("{} north", action.name())),
+                (true, format!("{} disease north", action.name())),
+            ] {
+                let handle = player_animation(
+                    &named_assets,
+                    PlayerId(0),
+                    action,
+                    Direction::North,
+                    diseased,
+                );
+                assert_eq!(handle, named_assets.animations.get(&name), "{}", name);
+            }
+        }
     }
 }
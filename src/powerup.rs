@@ -0,0 +1,245 @@
+use crate::{
+    animation::*,
+    asset_loaders::*,
+    bomb::{Bomb, FlameBrick, FlamePart},
+    grid::*,
+    player::{Player, PlayerAction, PlayerAnimation, PlayerDiedEvent},
+    state::*,
+};
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+
+#[derive(Default)]
+pub struct PowerupPlugin;
+
+impl Plugin for PowerupPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StartingPowerups>().add_system_set(
+            SystemSet::on_update(AppState::Game)
+                .with_system(give_starting_powerups)
+                .with_system(pickup_powerup)
+                .with_system(scatter_powerups),
+        );
+    }
+}
+
+/// A powerup lying in the arena, waiting to be picked up.
+#[derive(Component)]
+pub struct PowerupItem(pub Powerup);
+
+/// Powerups a player holds: the starting powerups, then the ones picked up
+/// during the round in pickup order.
+#[derive(Component, Default, Debug)]
+pub struct CollectedPowerups(pub Vec<Powerup>);
+
+impl CollectedPowerups {
+    pub fn is_diseased(&self) -> bool {
+        self.0
+            .iter()
+            .any(|powerup| matches!(powerup, Powerup::Disease | Powerup::SuperBadDisease))
+    }
+}
+
+/// Powerups every player starts a round with, and gets again on a respawn.
+#[derive(Default, Debug)]
+pub struct StartingPowerups(pub Vec<Powerup>);
+
+impl StartingPowerups {
+    /// The powerups the scheme marks as born with.
+    pub fn from_scheme(scheme: &Scheme) -> Self {
+        let powerups = Powerup::ALL
+            .iter()
+            .zip(scheme.powerup_infos.iter())
+            .filter(|(_powerup, info)| info.born_with)
+            .map(|(powerup, _info)| *powerup)
+            .collect();
+        StartingPowerups(powerups)
+    }
+}
+
+impl Powerup {
+    /// All powerups, in the order of the scheme files.
+    pub const ALL: [Powerup; 13] = [
+        Powerup::ExtraBomb,
+        Powerup::LongerFlame,
+        Powerup::Disease,
+        Powerup::Kick,
+        Powerup::Speed,
+        Powerup::Punch,
+        Powerup::Grab,
+        Powerup::Spooger,
+        Powerup::Goldflame,
+        Powerup::Trigger,
+        Powerup::Jelly,
+        Powerup::SuperBadDisease,
+        Powerup::Random,
+    ];
+
+    pub fn animation(&self) -> &'static str {
+        match self {
+            Powerup::ExtraBomb => "power bomb",
+            Powerup::LongerFlame => "power flame",
+            Powerup::Disease => "power disease",
+            Powerup::Kick => "power kicker",
+            Powerup::Speed => "power skate",
+            Powerup::Punch => "power punch",
+            Powerup::Grab => "power grab",
+            Powerup::Spooger => "power spooger",
+            Powerup::Goldflame => "power goldflame",
+            Powerup::Trigger => "power trigger",
+            Powerup::Jelly => "power jelly",
+            Powerup::SuperBadDisease => "power ebola",
+            Powerup::Random => "power random",
+        }
+    }
+}
+
+fn give_starting_powerups(
+    starting: Res<StartingPowerups>,
+    mut players: Query<&mut CollectedPowerups, Added<Player>>,
+) {
+    for mut collected in players.iter_mut() {
+        collected.0.extend(starting.0.iter().copied());
+    }
+}
+
+fn pickup_powerup(
+    mut commands: Commands,
+    mut players: Query<(&Position, &mut CollectedPowerups, &mut PlayerAnimation), With<Player>>,
+    items: Query<(Entity, &Position, &PowerupItem)>,
+) {
+    for (player_pos, mut collected, mut animation) in players.iter_mut() {
+        for (entity, pos, item) in items.iter() {
+            if pos == player_pos {
+                debug!("picked up {:?} at {:?}", item.0, pos);
+                collected.0.push(item.0);
+                animation.play(PlayerAction::Pickup);
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// When a player dies the powerups they collected are thrown onto random free
+/// cells of the arena, so they stay in play for the remaining players.
+fn scatter_powerups(
+    mut commands: Commands,
+    named_assets: Res<NamedAssets>,
+    animation_assets: Res<Assets<Animation>>,
+    mut died_events: EventReader<PlayerDiedEvent>,
+    cells: Query<(&Position, &Cell)>,
+    occupants: Query<&Position, Occupant>,
+) {
+    let mut free: Option<Vec<Position>> = None;
+
+    for event in died_events.iter() {
+        let free = free.get_or_insert_with(|| free_cells(&cells, &occupants));
+
+        let mut rng = rand::thread_rng();
+        free.shuffle(&mut rng);
+
+        for powerup in event.powerups.iter() {
+            // The arena can run out of room, the remaining powerups are lost.
+            let pos = match free.pop() {
+                Some(pos) => pos,
+                None => break,
+            };
+
+            debug!("scattering {:?} to {:?}", powerup, pos);
+
+            let animation = match named_assets.animations.get(powerup.animation()) {
+                Some(animation) => animation,
+                None => {
+                    warn!("no animation for powerup {:?}", powerup);
+                    continue;
+                }
+            };
+
+            commands
+                .spawn_bundle(AnimatedSpriteBundle::new(
+                    animation.clone(),
+                    &animation_assets,
+                    Default::default(),
+                ))
+                .insert(pos)
+                .insert(Offset(Vec3::ZERO))
+                .insert(SnapToGrid)
+                .insert(Layer::Item)
+                .insert(PowerupItem(*powerup));
+        }
+    }
+}
+
+/// Entities that block a cell for scattered powerups.
+type Occupant = Or<(
+    With<Bomb>,
+    With<FlamePart>,
+    With<FlameBrick>,
+    With<Player>,
+    With<PowerupItem>,
+)>;
+
+/// Blank cells that are not covered by flames and not occupied by a bomb, a
+/// player or another powerup.
+fn free_cells(
+    cells: &Query<(&Position, &Cell)>,
+    occupants: &Query<&Position, Occupant>,
+) -> Vec<Position> {
+    // Destroyed bricks are despawned, so a position without a cell entity is
+    // also blank.
+    let blocked: HashSet<Position> = cells
+        .iter()
+        .filter(|(_pos, cell)| cell != &&Cell::Blank)
+        .map(|(pos, _cell)| *pos)
+        .chain(occupants.iter().copied())
+        .collect();
+
+    (0..WIDTH)
+        .flat_map(|x| (0..HEIGHT).map(move |y| Position { x, y }))
+        .filter(|pos| !blocked.contains(pos))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::PlayerId;
+    use bevy::ecs::system::SystemState;
+
+    type Queries<'w, 's> = (
+        Query<'w, 's, (&'static Position, &'static Cell)>,
+        Query<'w, 's, &'static Position, Occupant>,
+    );
+
+    #[test]
+    fn free_cells_skip_blocked_cells() {
+        let mut world = World::new();
+        let at = |x, y| Position { x, y };
+        world.spawn().insert(at(0, 0)).insert(Cell::Solid);
+        world.spawn().insert(at(1, 0)).insert(Cell::Brick);
+        world.spawn().insert(at(2, 0)).insert(Cell::Blank);
+        world
+            .spawn()
+            .insert(at(3, 0))
+            .insert(Bomb { owner: PlayerId(0) });
+        world.spawn().insert(at(4, 0)).insert(FlamePart);
+        world.spawn().insert(at(5, 0)).insert(FlameBrick);
+        world.spawn().insert(at(6, 0)).insert(Player);
+        world
+            .spawn()
+            .insert(at(7, 0))
+            .insert(PowerupItem(Powerup::Kick));
+
+        let mut state: SystemState<Queries> = SystemState::new(&mut world);
+        let (cells, occupants) = state.get(&world);
+        let free = free_cells(&cells, &occupants);
+
+        assert!(free.contains(&at(2, 0)));
+        assert!(free.contains(&at(8, 0)));
+        for x in [0, 1, 3, 4, 5, 6, 7] {
+            assert!(!free.contains(&at(x, 0)), "{} should be blocked", x);
+        }
+        assert_eq!(free.len(), (WIDTH * HEIGHT) as usize - 7);
+    }
+}
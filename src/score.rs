@@ -0,0 +1,69 @@
+use crate::{
+    player::{PlayerDiedEvent, PlayerId},
+    state::*,
+};
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Scoreboard>()
+            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(start_round))
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(record_deaths));
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerScore {
+    pub wins: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub suicides: u32,
+}
+
+/// Kill statistics per player, for the current round and for the whole match.
+#[derive(Default, Debug)]
+pub struct Scoreboard {
+    pub round: HashMap<PlayerId, PlayerScore>,
+    pub total: HashMap<PlayerId, PlayerScore>,
+}
+
+impl Scoreboard {
+    pub fn round_score(&self, player: PlayerId) -> PlayerScore {
+        self.round.get(&player).copied().unwrap_or_default()
+    }
+
+    pub fn total_score(&self, player: PlayerId) -> PlayerScore {
+        self.total.get(&player).copied().unwrap_or_default()
+    }
+
+    /// Credit a death to the killer, or as a suicide if the player was killed
+    /// by their own flame.
+    pub fn record_death(&mut self, event: &PlayerDiedEvent) {
+        for scores in [&mut self.round, &mut self.total] {
+            let victim = scores.entry(event.id).or_default();
+            victim.deaths += 1;
+            if event.is_suicide() {
+                victim.suicides += 1;
+            } else {
+                scores.entry(event.killer).or_default().kills += 1;
+            }
+        }
+    }
+}
+
+fn start_round(mut scoreboard: ResMut<Scoreboard>) {
+    scoreboard.round.clear();
+}
+
+fn record_deaths(
+    mut scoreboard: ResMut<Scoreboard>,
+    mut died_events: EventReader<PlayerDiedEvent>,
+) {
+    for event in died_events.iter() {
+        scoreboard.record_death(event);
+    }
+}
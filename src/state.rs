@@ -1,5 +1,5 @@
 use crate::animation::Animation;
-use crate::asset_loaders::Scheme;
+use crate::asset_loaders::{RawSound, Scheme};
 use bevy::prelude::*;
 use std::collections::HashMap;
 use std::cmp::{Eq, PartialEq};
@@ -13,9 +13,25 @@ pub enum AppState {
     Game,
 }
 
+/// Number of player colors, there is a COLOR.PAL remap table for each.
+pub const PLAYER_COLORS: usize = 10;
+
 #[derive(Default)]
 pub struct NamedAssets {
     pub animations: HashMap<String, Handle<Animation>>,
-    pub sounds: HashMap<String, Handle<AudioSource>>,
+    /// Animations recolored for every player color, indexed by color.
+    pub colored_animations: HashMap<String, Vec<Handle<Animation>>>,
+    pub sounds: HashMap<String, Handle<RawSound>>,
     pub schemes: HashMap<String, Handle<Scheme>>,
 }
+
+impl NamedAssets {
+    /// The animation in the given player color. Falls back to the original
+    /// colors for animations that are not recolored.
+    pub fn colored_animation(&self, name: &str, color: usize) -> Option<&Handle<Animation>> {
+        self.colored_animations
+            .get(name)
+            .and_then(|handles| handles.get(color))
+            .or_else(|| self.animations.get(name))
+    }
+}